@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tokenizers_rs::BytePairEncoding;
+
+const WORDS: [&str; 8] = [
+    "token",
+    "tokenizer",
+    "merge",
+    "pair",
+    "byte",
+    "encoding",
+    "vocabulary",
+    "corpus",
+];
+
+fn synthetic_corpus(words: usize) -> String {
+    (0..words)
+        .map(|i| WORDS[(i * 7 + i / 3) % WORDS.len()])
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn bench_training(c: &mut Criterion) {
+    let mut group = c.benchmark_group("train");
+
+    for words in [100, 1_000, 10_000] {
+        let corpus = synthetic_corpus(words);
+        group.bench_with_input(BenchmarkId::from_parameter(words), &corpus, |b, corpus| {
+            b.iter(|| BytePairEncoding::from(black_box(corpus.clone()), 60))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_training_from_word_counts(c: &mut Criterion) {
+    let word_counts = WORDS
+        .iter()
+        .map(|word| (format!(" {word}"), 1_000))
+        .collect::<HashMap<String, usize>>();
+    let corpus = BytePairEncoding::builder().prepare_word_counts(word_counts);
+
+    c.bench_function("train_from_word_counts", |b| {
+        b.iter(|| corpus.retrain_with_vocab_size(black_box(60)))
+    });
+}
+
+fn bench_tokenize(c: &mut Criterion) {
+    let tokenizer = BytePairEncoding::from(synthetic_corpus(1_000), 60);
+    let text = synthetic_corpus(1_000);
+
+    c.bench_function("tokenize", |b| {
+        b.iter(|| tokenizer.tokenize(black_box(text.clone())))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_training,
+    bench_training_from_word_counts,
+    bench_tokenize
+);
+criterion_main!(benches);
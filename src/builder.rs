@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Error;
+use std::iter;
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BytePairEncoding, MergeRecord, TrainedCorpus, TrainingConfig};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CharacterSet {
+    Digits,
+    LatinBasic,
+    AsciiPrintable,
+}
+
+impl CharacterSet {
+    pub fn characters(self) -> Vec<String> {
+        let ranges = match self {
+            CharacterSet::Digits => vec!['0'..='9'],
+            CharacterSet::LatinBasic => vec!['A'..='Z', 'a'..='z'],
+            CharacterSet::AsciiPrintable => vec![' '..='~'],
+        };
+
+        ranges.into_iter().flatten().map(String::from).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeExhaustion {
+    #[default]
+    StopSilently,
+    Error,
+    ReturnStatus,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenOverflow {
+    #[default]
+    Error,
+    Truncate,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnknownWord {
+    #[default]
+    Error,
+    Replay,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeStrategy {
+    #[default]
+    Priority,
+    GreedyLeftToRight,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnknownCharacter {
+    #[default]
+    Error,
+    ByteFallback,
+    Unk,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BytePairEncodingBuilder {
+    pub(crate) config: TrainingConfig,
+}
+
+impl BytePairEncodingBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(self) -> Self {
+        Self::default()
+    }
+
+    pub fn lowercase_ascii(mut self, enabled: bool) -> Self {
+        self.config.normalizer.lowercase_ascii = enabled;
+        self
+    }
+
+    pub fn strip_accents(mut self, enabled: bool) -> Self {
+        self.config.normalizer.strip_accents = enabled;
+        self
+    }
+
+    pub fn strip_control_characters(mut self, enabled: bool) -> Self {
+        self.config.normalizer.strip_control_characters = enabled;
+        self
+    }
+
+    pub fn strip_invisible_marks(mut self, enabled: bool) -> Self {
+        self.config.normalizer.strip_invisible_marks = enabled;
+        self
+    }
+
+    pub fn initial_alphabet(mut self, alphabet: Vec<String>) -> Self {
+        self.config.initial_alphabet = alphabet;
+        self
+    }
+
+    pub fn include_character_set(mut self, set: CharacterSet) -> Self {
+        if !self.config.character_sets.contains(&set) {
+            self.config.character_sets.push(set);
+        }
+        self
+    }
+
+    pub fn estimated_unique_words(mut self, estimate: usize) -> Self {
+        self.config.estimated_unique_words = estimate;
+        self
+    }
+
+    pub fn unknown_word(mut self, policy: UnknownWord) -> Self {
+        self.config.unknown_word = policy;
+        self
+    }
+
+    pub fn unknown_character(mut self, policy: UnknownCharacter) -> Self {
+        self.config.unknown_character = policy;
+        self
+    }
+
+    pub fn merge_strategy(mut self, strategy: MergeStrategy) -> Self {
+        self.config.merge_strategy = strategy;
+        self
+    }
+
+    pub fn replace_unknown_with_bytes(self, enabled: bool) -> Self {
+        if enabled {
+            self.unknown_word(UnknownWord::Replay)
+                .unknown_character(UnknownCharacter::ByteFallback)
+        } else {
+            self.unknown_word(UnknownWord::Error)
+                .unknown_character(UnknownCharacter::Error)
+        }
+    }
+
+    pub fn on_merges_exhausted(mut self, policy: MergeExhaustion) -> Self {
+        self.config.merge_exhaustion = policy;
+        self
+    }
+
+    pub fn reserved_ids(mut self, count: usize) -> Self {
+        self.config.reserved_ids = count;
+        self
+    }
+
+    pub fn isolate_scripts(mut self, enabled: bool) -> Self {
+        self.config.isolate_scripts = enabled;
+        self
+    }
+
+    pub fn add_prefix_space(mut self, enabled: bool) -> Self {
+        self.config.add_prefix_space = enabled;
+        self
+    }
+
+    pub fn trim_corpus(mut self, enabled: bool) -> Self {
+        self.config.trim_corpus = enabled;
+        self
+    }
+
+    pub fn deduplicate_documents(mut self, enabled: bool) -> Self {
+        self.config.deduplicate_documents = enabled;
+        self
+    }
+
+    pub fn isolate_punctuation(mut self, enabled: bool) -> Self {
+        self.config.pre_tokenizer.isolate_punctuation = enabled;
+        self
+    }
+
+    pub fn group_punctuation(mut self, enabled: bool) -> Self {
+        self.config.pre_tokenizer.group_punctuation = enabled;
+        self
+    }
+
+    pub fn split_leading_boundary(mut self, enabled: bool) -> Self {
+        self.config.pre_tokenizer.split_leading_boundary = enabled;
+        self
+    }
+
+    pub fn split_on_unicode_whitespace(mut self, enabled: bool) -> Self {
+        self.config.pre_tokenizer.unicode_whitespace = enabled;
+        self
+    }
+
+    pub fn top_k_words(mut self, top_k: usize) -> Self {
+        self.config.top_k_words = Some(top_k);
+        self
+    }
+
+    pub fn max_tokens_per_input(mut self, max_tokens: usize) -> Self {
+        self.config.max_tokens_per_input = Some(max_tokens);
+        self
+    }
+
+    pub fn on_token_overflow(mut self, policy: TokenOverflow) -> Self {
+        self.config.token_overflow = policy;
+        self
+    }
+
+    pub fn add_special_token(mut self, token: &str) -> Self {
+        if !self
+            .config
+            .special_tokens
+            .iter()
+            .any(|special| special == token)
+        {
+            self.config.special_tokens.push(token.to_string());
+        }
+        self
+    }
+
+    pub fn required_words(mut self, words: Vec<String>) -> Self {
+        self.config.required_words = words;
+        self
+    }
+
+    pub fn max_edit_distance(mut self, max_distance: usize) -> Self {
+        self.config.max_edit_distance = Some(max_distance);
+        self
+    }
+
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.config.cache_capacity = capacity;
+        self
+    }
+
+    pub fn post_merge_rule(mut self, left: &str, right: &str) -> Self {
+        self.config
+            .post_merge_rules
+            .push((left.to_string(), right.to_string()));
+        self
+    }
+
+    pub fn with_special_tokens(mut self, enabled: bool) -> Self {
+        self.config.skip_special_tokens = !enabled;
+        self
+    }
+
+    pub fn special_ids_after_vocab(mut self, enabled: bool) -> Self {
+        self.config.special_ids_after_vocab = enabled;
+        self
+    }
+
+    pub fn render_leading_space(mut self, enabled: bool) -> Self {
+        self.config.render_leading_space = enabled;
+        self
+    }
+
+    pub fn mark_continuations(mut self, enabled: bool) -> Self {
+        self.config.mark_continuations = enabled;
+        self
+    }
+
+    pub fn case_insensitive_specials(mut self, enabled: bool) -> Self {
+        self.config.case_insensitive_specials = enabled;
+        self
+    }
+
+    pub fn max_alphabet_size(mut self, max_alphabet_size: usize) -> Self {
+        self.config.max_alphabet_size = Some(max_alphabet_size);
+        self
+    }
+
+    pub fn prepare(&self, corpus: String) -> TrainedCorpus {
+        self.prepare_chunks(iter::once(corpus))
+    }
+
+    pub fn prepare_chunks<I: Iterator<Item = String>>(&self, chunks: I) -> TrainedCorpus {
+        if self.config.deduplicate_documents {
+            return BytePairEncoding::prepare(Self::deduplicate_lines(chunks), &self.config);
+        }
+
+        BytePairEncoding::prepare(chunks, &self.config)
+    }
+
+    // A line may span several chunks, so the unfinished tail of each chunk
+    // is held back until the chunk that completes it arrives.
+    fn deduplicate_lines(chunks: impl Iterator<Item = String>) -> impl Iterator<Item = String> {
+        let mut seen = HashSet::new();
+        let mut partial = String::new();
+        chunks
+            .map(Some)
+            .chain(iter::once(None))
+            .map(move |chunk| {
+                let finished = chunk.is_none();
+                let text = mem::take(&mut partial) + chunk.as_deref().unwrap_or_default();
+
+                let mut kept = String::new();
+                for line in text.split_inclusive('\n') {
+                    if !finished && !line.ends_with('\n') {
+                        partial = line.to_string();
+                    } else if seen.insert(line.trim_end_matches('\n').to_string()) {
+                        kept.push_str(line);
+                    }
+                }
+                kept
+            })
+            .filter(|kept| !kept.is_empty())
+    }
+
+    pub fn prepare_documents(&self, mut documents: Vec<String>) -> TrainedCorpus {
+        if self.config.deduplicate_documents {
+            let mut seen = HashSet::new();
+            documents.retain(|document| seen.insert(document.clone()));
+        }
+
+        BytePairEncoding::prepare_streams(documents.into_iter().map(iter::once), &self.config)
+    }
+
+    pub fn prepare_word_counts(&self, word_counts: HashMap<String, usize>) -> TrainedCorpus {
+        BytePairEncoding::prepare_word_counts(word_counts, &self.config)
+    }
+
+    pub fn min_vocab_for_coverage(&self, corpus: &str, target: &str) -> Option<usize> {
+        let corpus = self.prepare(corpus.to_string());
+        let mut max_vocab_size = corpus.min_vocab_size();
+        loop {
+            let tokenizer = corpus.retrain_with_vocab_size(max_vocab_size).ok()?;
+            if tokenizer.tokenize(target.to_string()).is_ok() {
+                return Some(max_vocab_size);
+            }
+            if tokenizer.vocab_size < max_vocab_size {
+                return None;
+            }
+            max_vocab_size += 1;
+        }
+    }
+
+    pub fn train(&self, corpus: String, max_vocab_size: usize) -> Result<BytePairEncoding, Error> {
+        self.prepare(corpus).retrain_with_vocab_size(max_vocab_size)
+    }
+
+    pub fn train_with_observer(
+        &self,
+        corpus: String,
+        max_vocab_size: usize,
+        observer: impl FnMut(&MergeRecord),
+    ) -> Result<BytePairEncoding, Error> {
+        self.prepare(corpus)
+            .retrain_with_observer(max_vocab_size, observer)
+    }
+}
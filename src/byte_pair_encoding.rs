@@ -1,81 +1,2599 @@
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-use std::io::{Error, ErrorKind};
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, ErrorKind, Read};
+use std::iter;
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{PoisonError, RwLock};
 
+use rand::Rng;
+use regex::Regex;
+use serde::{Deserialize, Serialize, Serializer};
+use unicode_script::{Script, UnicodeScript};
+
+use crate::{
+    BytePairEncodingBuilder, InferenceModel, MergeExhaustion, MergeStrategy, Normalizer,
+    PreTokenizer, TokenOverflow, TrainedCorpus, TrainingConfig, UnknownCharacter, UnknownWord,
+};
+
+pub type Merge = (String, String);
+
+type WordTokens<'a> = Vec<(String, Cow<'a, [String]>)>;
+
+#[cfg(feature = "fxhash")]
+pub(crate) type Map<K, V> = HashMap<K, V, fxhash::FxBuildHasher>;
+#[cfg(not(feature = "fxhash"))]
+pub(crate) type Map<K, V> = HashMap<K, V>;
+
+fn serialize_sorted<S: Serializer, V: Serialize>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeRecord {
+    pub pair: Merge,
+    pub frequency: usize,
+    pub token: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrainingStatus {
+    Completed,
+    MergesExhausted { vocab_size: usize },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorpusStats {
+    pub unique_characters: usize,
+    pub unique_words: usize,
+    pub total_words: usize,
+    pub top_bigrams: Vec<(Merge, usize)>,
+}
+
+pub fn analyze_corpus(corpus: &str) -> CorpusStats {
+    BytePairEncoding::analyze(corpus)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenizerInfo {
+    pub vocab_size: usize,
+    pub alphabet_size: usize,
+    pub merge_count: usize,
+    pub special_tokens: Vec<String>,
+    pub normalizer: Normalizer,
+    pub pre_tokenizer: PreTokenizer,
+    pub add_prefix_space: bool,
+    pub unknown_word: UnknownWord,
+    pub unknown_character: UnknownCharacter,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub input: String,
+    pub expected: Vec<String>,
+    pub actual: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrainingEstimate {
+    pub unique_words: usize,
+    pub alphabet_size: usize,
+    pub merges: usize,
+    pub complexity: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PaddingSide {
+    Left,
+    #[default]
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
+
+const CACHE_SHARDS: usize = 16;
+
+#[derive(Default)]
+struct ReplayCache {
+    shards: [RwLock<HashMap<String, Vec<String>>>; CACHE_SHARDS],
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    entries: AtomicUsize,
+}
+
+impl ReplayCache {
+    fn shard(&self, word: &str) -> &RwLock<HashMap<String, Vec<String>>> {
+        let mut hasher = DefaultHasher::new();
+        word.hash(&mut hasher);
+
+        &self.shards[hasher.finish() as usize % CACHE_SHARDS]
+    }
+
+    fn get(&self, word: &str) -> Option<Vec<String>> {
+        let shard = self
+            .shard(word)
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let tokens = shard.get(word).cloned();
+
+        match tokens {
+            Some(_) => self.hits.fetch_add(1, AtomicOrdering::Relaxed),
+            None => self.misses.fetch_add(1, AtomicOrdering::Relaxed),
+        };
+        tokens
+    }
+
+    fn insert(&self, word: &str, tokens: &[String], capacity: usize) {
+        let reserved = self.entries.fetch_update(
+            AtomicOrdering::Relaxed,
+            AtomicOrdering::Relaxed,
+            |entries| (entries < capacity).then_some(entries + 1),
+        );
+        if reserved.is_err() {
+            return;
+        }
+
+        let mut shard = self
+            .shard(word)
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if shard.insert(word.to_string(), tokens.to_vec()).is_some() {
+            self.entries.fetch_sub(1, AtomicOrdering::Relaxed);
+        }
+    }
+
+    fn clear(&self) {
+        for shard in self.shards.iter() {
+            let mut shard = shard.write().unwrap_or_else(PoisonError::into_inner);
+            self.entries.fetch_sub(shard.len(), AtomicOrdering::Relaxed);
+            shard.clear();
+        }
+        self.hits.store(0, AtomicOrdering::Relaxed);
+        self.misses.store(0, AtomicOrdering::Relaxed);
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Atomic(&'a str),
+}
+
+enum PreToken {
+    Atomic(String),
+    Word(String),
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct BytePairEncoding {
     pub vocab_size: usize,
+    #[serde(serialize_with = "serialize_sorted")]
     pub tokenizer: HashMap<String, Vec<String>>,
+    pub merges: Vec<Merge>,
+    alphabet: Vec<String>,
+    config: TrainingConfig,
+    frozen: bool,
+    #[serde(serialize_with = "serialize_sorted")]
+    frequencies: HashMap<String, usize>,
+    #[serde(serialize_with = "serialize_sorted")]
+    scores: HashMap<String, f64>,
+    training_status: Option<TrainingStatus>,
+    joined_tokens: Vec<String>,
+    appended_specials: usize,
+    imported_vocab: Vec<String>,
+    #[serde(skip)]
+    rules: Vec<(Regex, Regex)>,
+    #[serde(skip)]
+    id_tokens: Vec<String>,
+    #[serde(skip)]
+    token_ids: HashMap<String, usize>,
+    #[serde(skip)]
+    merge_lookup: HashMap<String, HashSet<String>>,
+    #[serde(skip)]
+    cache: ReplayCache,
 }
 
-impl BytePairEncoding {
-    const PUNCTUATION: [char; 6] = [' ', '.', ',', '!', '?', '\n'];
-    const START_TOKEN: &str = "<|startoftext|>";
-    const END_TOKEN: &str = "<|endoftext|>";
+impl BytePairEncoding {
+    #[cfg(feature = "bincode")]
+    const BIN_MAGIC: [u8; 4] = *b"BPE\0";
+    #[cfg(feature = "bincode")]
+    const BIN_VERSION: u8 = 1;
+    const LEADING_SPACE: char = '\u{2581}';
+    const CONTINUATION_MARKER: &'static str = "##";
+    pub(crate) const PUNCTUATION: [char; 6] = [' ', '.', ',', '!', '?', '\n'];
+    const START_TOKEN: &str = "<|startoftext|>";
+    const END_TOKEN: &str = "<|endoftext|>";
+    const UNK_TOKEN: &str = "<|unk|>";
+    const TOP_BIGRAMS: usize = 10;
+
+    pub fn from(corpus: String, max_vocab_size: usize) -> Self {
+        Self::builder()
+            .train(corpus, max_vocab_size)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    fn restore(mut self) -> Result<Self, Error> {
+        self.rules = Self::compile_rules(&self.config.post_merge_rules)?;
+        Self::check_token_limit(&self.config)?;
+        self.index_vocab();
+        Ok(self)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.to_json()?)
+    }
+
+    pub fn save_vocab_csv(&self, path: &Path) -> Result<(), Error> {
+        let mut csv = String::from("token,id,creation_frequency\r\n");
+        for (position, token) in self.vocab().into_iter().enumerate() {
+            let frequency = self
+                .frequencies
+                .get(&token)
+                .map(|frequency| frequency.to_string())
+                .unwrap_or_default();
+
+            csv.push_str(&format!(
+                "{},{},{}\r\n",
+                Self::escape_csv(&token),
+                position + self.config.reserved_ids,
+                frequency
+            ));
+        }
+
+        fs::write(path, csv)
+    }
+
+    fn escape_csv(field: &str) -> String {
+        if !field.contains([',', '"', '\n', '\r']) {
+            return field.to_string();
+        }
+
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }
+
+    #[cfg(feature = "bincode")]
+    pub fn save_bin(&self, path: &Path) -> Result<(), Error> {
+        let mut bytes = Self::BIN_MAGIC.to_vec();
+        bytes.push(Self::BIN_VERSION);
+        bincode::serde::encode_into_std_write(self, &mut bytes, bincode::config::standard())
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+
+        fs::write(path, bytes)
+    }
+
+    #[cfg(feature = "bincode")]
+    pub fn load_bin(path: &Path) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        let payload = bytes
+            .strip_prefix(Self::BIN_MAGIC.as_slice())
+            .ok_or(Error::new(
+                ErrorKind::InvalidData,
+                "File is not a binary tokenizer",
+            ))?;
+
+        match payload.split_first() {
+            Some((&Self::BIN_VERSION, payload)) => {
+                let (tokenizer, _) = bincode::serde::decode_from_slice::<Self, _>(
+                    payload,
+                    bincode::config::standard(),
+                )
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+                tokenizer.restore()
+            }
+            Some((version, _)) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported binary tokenizer version {version}"),
+            )),
+            None => Err(Error::new(
+                ErrorKind::InvalidData,
+                "Binary tokenizer is missing its version",
+            )),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_gpt2_files(vocab_path: &Path, merges_path: &Path) -> Result<Self, Error> {
+        let vocab: HashMap<String, usize> = serde_json::from_str(&fs::read_to_string(vocab_path)?)?;
+        let bytes = Self::gpt2_byte_chars()
+            .into_iter()
+            .zip(0..=u8::MAX)
+            .collect::<HashMap<char, u8>>();
+
+        let mut merges = vec![];
+        for line in fs::read_to_string(merges_path)?.lines() {
+            if line.starts_with("#version") || line.is_empty() {
+                continue;
+            }
+
+            let (left, right) = line.split_once(' ').ok_or(Error::new(
+                ErrorKind::InvalidData,
+                format!("Merge line {line:?} is not a space-separated pair"),
+            ))?;
+            merges.push((
+                Self::unescape_gpt2(left, &bytes),
+                Self::unescape_gpt2(right, &bytes),
+            ));
+        }
+
+        let mut ids = vocab
+            .iter()
+            .map(|(token, id)| (*id, Self::unescape_gpt2(token, &bytes)))
+            .collect::<Vec<(usize, String)>>();
+        ids.sort();
+        if ids
+            .iter()
+            .enumerate()
+            .any(|(position, (id, _))| position != *id)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Vocabulary ids must run from 0 without gaps or repeats",
+            ));
+        }
+
+        let mut alphabet = vocab
+            .keys()
+            .map(|token| Self::unescape_gpt2(token, &bytes))
+            .filter(|token| token.chars().count() == 1)
+            .chain(Self::PUNCTUATION.iter().map(|c| c.to_string()))
+            .collect::<Vec<String>>();
+        alphabet.sort();
+        alphabet.dedup();
+
+        let tokenizer = Self::PUNCTUATION
+            .iter()
+            .map(|c| (c.to_string(), vec![c.to_string()]))
+            .collect::<HashMap<String, Vec<String>>>();
+
+        let mut bpe = BytePairEncoding {
+            vocab_size: alphabet.len() + merges.len(),
+            tokenizer,
+            merges,
+            alphabet,
+            config: TrainingConfig {
+                unknown_word: UnknownWord::Replay,
+                ..Default::default()
+            },
+            frozen: false,
+            frequencies: HashMap::new(),
+            scores: HashMap::new(),
+            training_status: None,
+            joined_tokens: vec![],
+            appended_specials: 0,
+            imported_vocab: ids.into_iter().map(|(_, token)| token).collect(),
+            rules: vec![],
+            id_tokens: vec![],
+            token_ids: HashMap::new(),
+            merge_lookup: HashMap::new(),
+            cache: ReplayCache::default(),
+        };
+        bpe.index_vocab();
+        bpe.validate()?;
+
+        Ok(bpe)
+    }
+
+    pub fn merges_to_string(&self) -> String {
+        let chars = Self::gpt2_byte_chars();
+        self.merges.iter().fold(
+            String::from("#version: 0.2\n"),
+            |mut merges, (left, right)| {
+                merges.push_str(&Self::escape_gpt2(left, &chars));
+                merges.push(' ');
+                merges.push_str(&Self::escape_gpt2(right, &chars));
+                merges.push('\n');
+                merges
+            },
+        )
+    }
+
+    pub fn to_rust_source(&self, ident: &str) -> Result<String, Error> {
+        let list = |tokens: Vec<String>| {
+            tokens
+                .iter()
+                .map(|token| format!("    {token:?},\n"))
+                .collect::<String>()
+        };
+        let merges = self
+            .merges
+            .iter()
+            .map(|(left, right)| format!("    ({left:?}, {right:?}),\n"))
+            .collect::<String>();
+
+        let config = serde_json::to_string(&self.config)?;
+
+        Ok(format!(
+            "pub mod {ident} {{\n\
+             pub const CONFIG: &str = {config:?};\n\
+             pub const RESERVED_IDS: usize = {};\n\
+             pub const VOCAB: &[&str] = &[\n{}];\n\
+             pub const SPECIAL_TOKENS: &[&str] = &[\n{}];\n\
+             pub const ALPHABET: &[&str] = &[\n{}];\n\
+             pub const MERGES: &[(&str, &str)] = &[\n{merges}];\n\
+             }}\n",
+            self.config.reserved_ids,
+            list(self.vocab()),
+            list(self.special_tokens()),
+            list(self.alphabet.clone()),
+        ))
+    }
+
+    pub fn from_rust_consts(
+        config: &str,
+        vocab: &[&str],
+        alphabet: &[&str],
+        merges: &[(&str, &str)],
+    ) -> Result<Self, Error> {
+        let config = serde_json::from_str::<TrainingConfig>(config)?;
+        let alphabet = alphabet
+            .iter()
+            .map(|token| token.to_string())
+            .collect::<Vec<String>>();
+        let merges = merges
+            .iter()
+            .map(|(left, right)| (left.to_string(), right.to_string()))
+            .collect::<Vec<Merge>>();
+
+        let mut bpe = BytePairEncoding {
+            vocab_size: alphabet.len() + merges.len(),
+            tokenizer: HashMap::new(),
+            merges,
+            alphabet,
+            config: TrainingConfig {
+                unknown_word: UnknownWord::Replay,
+                ..config
+            },
+            frozen: false,
+            frequencies: HashMap::new(),
+            scores: HashMap::new(),
+            training_status: None,
+            joined_tokens: vec![],
+            appended_specials: 0,
+            imported_vocab: vec![],
+            rules: vec![],
+            id_tokens: vec![],
+            token_ids: HashMap::new(),
+            merge_lookup: HashMap::new(),
+            cache: ReplayCache::default(),
+        }
+        .restore()?;
+        bpe.tokenizer = bpe.word_map(vec![]);
+
+        // Tokens joined by post-merge rules during training are the only
+        // vocabulary entries the alphabet and merges cannot reproduce.
+        let derived = bpe.id_tokens.iter().collect::<HashSet<&String>>();
+        bpe.joined_tokens = vocab
+            .iter()
+            .filter(|token| !derived.contains(&token.to_string()))
+            .map(|token| token.to_string())
+            .collect();
+        bpe.vocab_size += bpe.joined_tokens.len();
+
+        // Specials added after training sit at the end of the vocabulary.
+        for appended in 0..=bpe.config.special_tokens.len() {
+            bpe.appended_specials = appended;
+            bpe.index_vocab();
+            if bpe.id_tokens == vocab {
+                return Ok(bpe);
+            }
+        }
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            "Embedded vocabulary does not match its alphabet and merges",
+        ))
+    }
+
+    fn gpt2_byte_chars() -> [char; 256] {
+        let mut chars = ['\0'; 256];
+        let mut shifted = 0;
+        for byte in 0..=u8::MAX {
+            chars[usize::from(byte)] = match byte {
+                b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF => char::from(byte),
+                _ => {
+                    shifted += 1;
+                    char::from_u32(255 + shifted).unwrap()
+                }
+            };
+        }
+
+        chars
+    }
+
+    fn escape_gpt2(token: &str, chars: &[char; 256]) -> String {
+        token.bytes().map(|byte| chars[usize::from(byte)]).collect()
+    }
+
+    fn unescape_gpt2(token: &str, bytes: &HashMap<char, u8>) -> String {
+        let mut decoded = vec![];
+        for c in token.chars() {
+            match bytes.get(&c) {
+                Some(byte) => decoded.push(*byte),
+                None => decoded.extend(c.to_string().bytes()),
+            }
+        }
+
+        String::from_utf8(decoded).unwrap_or_else(|_| token.to_string())
+    }
+
+    pub fn builder() -> BytePairEncodingBuilder {
+        BytePairEncodingBuilder::new()
+    }
+
+    pub fn normalizer(&self) -> &Normalizer {
+        &self.config.normalizer
+    }
+
+    pub fn pre_tokenizer(&self) -> &PreTokenizer {
+        &self.config.pre_tokenizer
+    }
+
+    pub fn alphabet(&self) -> &[String] {
+        &self.alphabet
+    }
+
+    pub fn training_config(&self) -> &TrainingConfig {
+        &self.config
+    }
+
+    pub fn training_status(&self) -> Option<TrainingStatus> {
+        self.training_status
+    }
+
+    pub fn info(&self) -> TokenizerInfo {
+        TokenizerInfo {
+            vocab_size: self.vocab_size,
+            alphabet_size: self.alphabet.len(),
+            merge_count: self.merges.len(),
+            special_tokens: self.special_tokens(),
+            normalizer: self.config.normalizer,
+            pre_tokenizer: self.config.pre_tokenizer,
+            add_prefix_space: self.config.add_prefix_space,
+            unknown_word: self.config.unknown_word,
+            unknown_character: self.config.unknown_character,
+        }
+    }
+
+    pub fn from_chunks<I: Iterator<Item = String>>(
+        chunks: I,
+        max_vocab_size: usize,
+    ) -> Result<Self, Error> {
+        Self::builder()
+            .prepare_chunks(chunks)
+            .retrain_with_vocab_size(max_vocab_size)
+    }
+
+    pub fn from_documents(documents: Vec<String>, max_vocab_size: usize) -> Result<Self, Error> {
+        Self::builder()
+            .prepare_documents(documents)
+            .retrain_with_vocab_size(max_vocab_size)
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R, max_vocab_size: usize) -> Result<Self, Error> {
+        let mut corpus = String::new();
+        reader.read_to_string(&mut corpus)?;
+
+        Self::builder().train(corpus, max_vocab_size)
+    }
+
+    pub(crate) fn prepare<I: Iterator<Item = String>>(
+        chunks: I,
+        config: &TrainingConfig,
+    ) -> TrainedCorpus {
+        Self::prepare_streams(iter::once(chunks), config)
+    }
+
+    pub(crate) fn prepare_streams<S, I>(streams: S, config: &TrainingConfig) -> TrainedCorpus
+    where
+        S: Iterator<Item = I>,
+        I: Iterator<Item = String>,
+    {
+        let mut initial_alphabet = config.initial_alphabet.clone();
+        for set in config.character_sets.iter() {
+            initial_alphabet.extend(set.characters());
+        }
+        if config.unknown_character == UnknownCharacter::ByteFallback {
+            initial_alphabet.extend((0..=u8::MAX).map(Self::byte_token));
+        }
+
+        let reserved = Self::build_vocablary("", &initial_alphabet)
+            .into_iter()
+            .collect::<HashSet<String>>();
+        let mut alphabet = reserved.clone();
+        let mut words = Map::<Vec<String>, usize>::with_capacity_and_hasher(
+            config.estimated_unique_words,
+            Default::default(),
+        );
+        let mut found_special = false;
+        for chunks in streams {
+            found_special |= Self::count_stream(chunks, config, &mut alphabet, &mut words);
+        }
+        let words = Self::finish_words(words, &mut alphabet, &reserved, config);
+
+        TrainedCorpus {
+            alphabet: Self::into_alphabet(alphabet),
+            only_special_tokens: found_special
+                && words.keys().all(|word| word.concat().trim().is_empty()),
+            words,
+            config: config.clone(),
+        }
+    }
+
+    fn count_stream<I: Iterator<Item = String>>(
+        chunks: I,
+        config: &TrainingConfig,
+        alphabet: &mut HashSet<String>,
+        words: &mut Map<Vec<String>, usize>,
+    ) -> bool {
+        let mut carry = String::new();
+        let mut found_special = false;
+
+        let mut specials = vec![Self::START_TOKEN.to_string(), Self::END_TOKEN.to_string()];
+        if config.unknown_character == UnknownCharacter::Unk {
+            specials.push(Self::UNK_TOKEN.to_string());
+        }
+        specials.extend(config.special_tokens.iter().cloned());
+        specials.retain(|special| !special.is_empty());
+
+        let mut chunks = chunks.enumerate().peekable();
+        while let Some((i, chunk)) = chunks.next() {
+            let segments = Self::split_atomic(&chunk, &specials, config.case_insensitive_specials);
+
+            let last = segments.len().saturating_sub(1);
+            for (position, segment) in segments.into_iter().enumerate() {
+                let segment = match segment {
+                    Segment::Atomic(_) => {
+                        found_special = true;
+                        for (word, freq) in Self::text_to_map(&[mem::take(&mut carry)], 0) {
+                            *words.entry(word).or_insert(0) += freq;
+                        }
+                        continue;
+                    }
+                    Segment::Text(segment) => segment,
+                };
+
+                let first = i == 0 && position == 0;
+                let mut segment = config.normalizer.normalize(segment);
+                if config.trim_corpus {
+                    let last = position == last && chunks.peek().is_none();
+                    segment = Self::trim_corpus(&segment, first, last);
+                }
+
+                // A carried word continues the text, so only the true start
+                // of a segment may split off its leading boundary.
+                let pre_tokenizer = PreTokenizer {
+                    split_leading_boundary: config.pre_tokenizer.split_leading_boundary
+                        && carry.is_empty(),
+                    ..config.pre_tokenizer
+                };
+                let mut text = mem::take(&mut carry) + &segment;
+                if first && config.add_prefix_space {
+                    text = Self::with_prefix_space(text);
+                }
+                alphabet.extend(Self::build_vocablary(&text, &[]));
+
+                let mut pre_tokenized = pre_tokenizer.pre_tokenize(&text);
+                carry = pre_tokenized.pop().unwrap_or_default();
+
+                for (word, freq) in Self::text_to_map(&pre_tokenized, 0) {
+                    *words.entry(word).or_insert(0) += freq;
+                }
+            }
+        }
+
+        for (word, freq) in Self::text_to_map(&[carry], 0) {
+            *words.entry(word).or_insert(0) += freq;
+        }
+
+        found_special
+    }
+
+    pub(crate) fn prepare_word_counts(
+        word_counts: HashMap<String, usize>,
+        config: &TrainingConfig,
+    ) -> TrainedCorpus {
+        let mut corpus = Self::prepare(iter::empty(), config);
+        let reserved = corpus.alphabet.into_iter().collect::<HashSet<String>>();
+        let mut alphabet = reserved.clone();
+        let mut words = Map::<Vec<String>, usize>::with_capacity_and_hasher(
+            word_counts.len(),
+            Default::default(),
+        );
+
+        for (word, freq) in word_counts {
+            let word = config.normalizer.normalize(&word);
+            if word.is_empty() {
+                continue;
+            }
+
+            alphabet.extend(word.chars().map(|c| c.to_string()));
+
+            let splitted_word = word.chars().map(|c| c.to_string()).collect::<Vec<String>>();
+            *words.entry(splitted_word).or_insert(0) += freq;
+        }
+
+        let words = Self::finish_words(words, &mut alphabet, &reserved, config);
+
+        corpus.alphabet = Self::into_alphabet(alphabet);
+        corpus.words = words;
+        corpus
+    }
+
+    fn finish_words(
+        mut words: Map<Vec<String>, usize>,
+        alphabet: &mut HashSet<String>,
+        reserved: &HashSet<String>,
+        config: &TrainingConfig,
+    ) -> Map<Vec<String>, usize> {
+        if let Some(max_alphabet_size) = config.max_alphabet_size {
+            Self::cap_alphabet(
+                &mut words,
+                alphabet,
+                reserved,
+                max_alphabet_size,
+                config.unknown_character,
+            );
+        }
+
+        let mut words = Self::retain_top_k_words(words, config.top_k_words);
+        if config.mark_continuations {
+            words = Self::mark_continuations(words, alphabet);
+        }
+
+        words
+    }
+
+    fn cap_alphabet(
+        words: &mut Map<Vec<String>, usize>,
+        alphabet: &mut HashSet<String>,
+        reserved: &HashSet<String>,
+        max_alphabet_size: usize,
+        unknown_character: UnknownCharacter,
+    ) {
+        let mut frequencies = HashMap::<&String, usize>::new();
+        for (word, freq) in words.iter() {
+            for piece in word.iter().filter(|piece| !reserved.contains(*piece)) {
+                *frequencies.entry(piece).or_insert(0) += freq;
+            }
+        }
+
+        let mut ranked = frequencies.into_iter().collect::<Vec<(&String, usize)>>();
+        ranked.sort_by(|(a, a_freq), (b, b_freq)| b_freq.cmp(a_freq).then(a.cmp(b)));
+        let reserved_size = Self::into_alphabet(reserved.iter().cloned()).len();
+        let kept = ranked
+            .into_iter()
+            .take(max_alphabet_size.saturating_sub(reserved_size))
+            .map(|(piece, _)| piece.clone())
+            .collect::<HashSet<String>>();
+
+        alphabet.retain(|piece| reserved.contains(piece) || kept.contains(piece));
+
+        let replaced = words
+            .drain()
+            .filter_map(|(word, freq)| {
+                let mut pieces = vec![];
+                for piece in word {
+                    if alphabet.contains(&piece) {
+                        pieces.push(piece);
+                        continue;
+                    }
+
+                    match unknown_character {
+                        UnknownCharacter::Error => return None,
+                        UnknownCharacter::ByteFallback => {
+                            pieces.extend(piece.bytes().map(Self::byte_token))
+                        }
+                        UnknownCharacter::Unk => pieces.push(Self::UNK_TOKEN.to_string()),
+                    }
+                }
+                Some((pieces, freq))
+            })
+            .collect::<Vec<(Vec<String>, usize)>>();
+        for (word, freq) in replaced {
+            *words.entry(word).or_insert(0) += freq;
+        }
+    }
+
+    fn mark_continuations(
+        words: Map<Vec<String>, usize>,
+        alphabet: &mut HashSet<String>,
+    ) -> Map<Vec<String>, usize> {
+        words
+            .into_iter()
+            .map(|(word, freq)| {
+                let word = Self::mark_pieces(word);
+                alphabet.extend(word.iter().cloned());
+                (word, freq)
+            })
+            .collect()
+    }
+
+    fn mark_pieces(pieces: Vec<String>) -> Vec<String> {
+        let mut prefix = String::new();
+        pieces
+            .into_iter()
+            .map(|piece| {
+                let marked = Self::mark_piece(&prefix, &piece, true);
+                prefix.push_str(&piece);
+                marked
+            })
+            .collect()
+    }
+
+    fn mark_piece(prefix: &str, piece: &str, mark_continuations: bool) -> String {
+        if mark_continuations && !prefix.trim_start().is_empty() {
+            return format!("{}{piece}", Self::CONTINUATION_MARKER);
+        }
+
+        piece.to_string()
+    }
+
+    fn join_pair(&self, left: &str, right: &str) -> String {
+        Self::join(left, right, self.config.mark_continuations)
+    }
+
+    fn join(left: &str, right: &str, mark_continuations: bool) -> String {
+        format!(
+            "{left}{}",
+            Self::strip_continuation(right, mark_continuations)
+        )
+    }
+
+    fn surface(tokens: &[String], mark_continuations: bool) -> String {
+        tokens
+            .iter()
+            .map(|token| Self::strip_continuation(token, mark_continuations))
+            .collect()
+    }
+
+    fn strip_continuation(token: &str, mark_continuations: bool) -> &str {
+        match token.strip_prefix(Self::CONTINUATION_MARKER) {
+            Some(rest) if mark_continuations => rest,
+            _ => token,
+        }
+    }
+
+    fn retain_top_k_words(
+        words: Map<Vec<String>, usize>,
+        top_k: Option<usize>,
+    ) -> Map<Vec<String>, usize> {
+        let Some(top_k) = top_k else {
+            return words;
+        };
+        if words.len() <= top_k {
+            return words;
+        }
+
+        let mut ranked = words.into_iter().collect::<Vec<(Vec<String>, usize)>>();
+        ranked.sort_by(|(a, a_freq), (b, b_freq)| b_freq.cmp(a_freq).then(a.cmp(b)));
+        ranked.truncate(top_k);
+
+        ranked.into_iter().collect()
+    }
+
+    pub fn from_vocab(words: Vec<String>, max_vocab_size: usize) -> Self {
+        let mut corpus = Self::builder()
+            .unknown_word(UnknownWord::Replay)
+            .prepare_chunks(iter::empty());
+        corpus.alphabet = Self::into_alphabet(Self::build_vocablary(&words.concat(), &[]));
+        corpus.words = Self::text_to_map(&words, words.len());
+
+        let vocab_size = corpus.alphabet.len();
+        assert!(
+            max_vocab_size >= vocab_size,
+            "vocab_size {} must be at least the size of the alphabet {}",
+            max_vocab_size,
+            vocab_size
+        );
+
+        Self::learn(&corpus, vocab_size, &mut |_| {})
+            .expect("training with the default builder never fails")
+    }
+
+    pub fn from_n_merges(corpus: String, n: usize) -> Self {
+        let corpus = Self::builder().prepare(corpus);
+        let max_vocab_size = corpus.alphabet.len() + n;
+
+        Self::learn(&corpus, max_vocab_size, &mut |_| {})
+            .expect("training with the default builder never fails")
+    }
+
+    pub fn min_vocab_for_coverage(corpus: &str, target: &str) -> Option<usize> {
+        Self::builder().min_vocab_for_coverage(corpus, target)
+    }
+
+    pub fn estimate_training_cost(corpus: &str, max_vocab_size: usize) -> TrainingEstimate {
+        let corpus = Self::builder().prepare(corpus.to_string());
+        let merges = max_vocab_size.saturating_sub(corpus.alphabet.len());
+        let pieces = corpus.words.keys().map(Vec::len).sum::<usize>();
+
+        TrainingEstimate {
+            unique_words: corpus.words.len(),
+            alphabet_size: corpus.alphabet.len(),
+            merges,
+            complexity: merges.saturating_mul(pieces),
+        }
+    }
+
+    pub(crate) fn train(
+        corpus: &TrainedCorpus,
+        max_vocab_size: usize,
+        observer: &mut dyn FnMut(&MergeRecord),
+    ) -> Result<Self, Error> {
+        if corpus.only_special_tokens {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Corpus contains nothing but special tokens",
+            ));
+        }
+
+        let min_vocab_size = corpus.min_vocab_size();
+        if max_vocab_size < min_vocab_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "vocab_size {max_vocab_size} is below the minimum of {min_vocab_size}: \
+                     {} alphabet characters plus at least one merge",
+                    corpus.alphabet.len()
+                ),
+            ));
+        }
+
+        Self::learn(corpus, max_vocab_size, observer)
+    }
+
+    fn learn(
+        corpus: &TrainedCorpus,
+        max_vocab_size: usize,
+        observer: &mut dyn FnMut(&MergeRecord),
+    ) -> Result<Self, Error> {
+        let rules = Self::compile_rules(&corpus.config.post_merge_rules)?;
+        Self::check_token_limit(&corpus.config)?;
+        let mut vocab_size = corpus.alphabet.len();
+        let mut words = corpus.words.clone();
+        let mut merges = vec![];
+        let mut frequencies = HashMap::<String, usize>::new();
+
+        while max_vocab_size > vocab_size {
+            let Some((pair, frequency)) =
+                Self::get_most_frequent_pair(&words, corpus.config.isolate_scripts)
+            else {
+                if corpus.config.merge_exhaustion == MergeExhaustion::Error {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "No more merges possible at vocab_size {vocab_size}, \
+                             before reaching {max_vocab_size}"
+                        ),
+                    ));
+                }
+                break;
+            };
+
+            let token = Self::join(&pair[0], &pair[1], corpus.config.mark_continuations);
+            observer(&MergeRecord {
+                pair: (pair[0].clone(), pair[1].clone()),
+                frequency,
+                token: token.clone(),
+            });
+            *frequencies.entry(token).or_insert(0) += frequency;
+            merges.push((pair[0].clone(), pair[1].clone()));
+            words = Self::merge_by_pair(words, pair, corpus.config.mark_continuations);
+            vocab_size += 1;
+        }
+
+        let words_len = words.len();
+        let mut tokenizer_mapper = words.into_keys().fold(
+            HashMap::<String, Vec<String>>::with_capacity(words_len),
+            |mut map, word| {
+                map.insert(Self::surface(&word, corpus.config.mark_continuations), word);
+                map
+            },
+        );
+        let mut joined_tokens = vec![];
+        for tokens in tokenizer_mapper.values_mut() {
+            if let Some(joined) = Self::join_by_rules(&rules, tokens, |_| true) {
+                joined_tokens.extend(joined.iter().filter(|t| !tokens.contains(t)).cloned());
+                *tokens = joined;
+            }
+        }
+        for punctuation in Self::PUNCTUATION.iter().map(|c| c.to_string()) {
+            tokenizer_mapper
+                .entry(punctuation.clone())
+                .or_insert(vec![punctuation]);
+        }
+
+        let training_status = match corpus.config.merge_exhaustion {
+            MergeExhaustion::ReturnStatus if vocab_size < max_vocab_size => {
+                Some(TrainingStatus::MergesExhausted { vocab_size })
+            }
+            MergeExhaustion::ReturnStatus => Some(TrainingStatus::Completed),
+            _ => None,
+        };
+
+        let mut known = corpus
+            .alphabet
+            .iter()
+            .chain(frequencies.keys())
+            .cloned()
+            .collect::<HashSet<String>>();
+        for word in corpus.config.required_words.iter() {
+            tokenizer_mapper.insert(word.clone(), vec![word.clone()]);
+            if known.insert(word.clone()) {
+                vocab_size += 1;
+            }
+        }
+        joined_tokens.sort();
+        joined_tokens.dedup();
+        joined_tokens.retain(|token| known.insert(token.clone()));
+        vocab_size += joined_tokens.len();
+
+        let mut bpe = BytePairEncoding {
+            vocab_size,
+            tokenizer: tokenizer_mapper,
+            merges,
+            alphabet: corpus.alphabet.clone(),
+            config: TrainingConfig {
+                max_vocab_size,
+                ..corpus.config.clone()
+            },
+            frozen: false,
+            scores: Self::scores(&frequencies),
+            frequencies,
+            training_status,
+            joined_tokens,
+            appended_specials: 0,
+            imported_vocab: vec![],
+            rules,
+            id_tokens: vec![],
+            token_ids: HashMap::new(),
+            merge_lookup: HashMap::new(),
+            cache: ReplayCache::default(),
+        };
+        bpe.index_vocab();
+
+        Ok(bpe)
+    }
+
+    pub fn token_score(&self, token: &str) -> Option<f64> {
+        self.scores.get(token).copied()
+    }
+
+    fn scores(frequencies: &HashMap<String, usize>) -> HashMap<String, f64> {
+        let total = frequencies.values().sum::<usize>() as f64;
+
+        frequencies
+            .iter()
+            .map(|(token, frequency)| (token.clone(), (*frequency as f64 / total).ln()))
+            .collect()
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn add_special_token(&mut self, token: &str) -> Result<(), Error> {
+        if self.is_special(token) {
+            return Ok(());
+        }
+        if self.frozen {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot add special token {token:?} to a frozen tokenizer"),
+            ));
+        }
+
+        self.config.special_tokens.push(token.to_string());
+        self.appended_specials += 1;
+        self.index_vocab();
+        Ok(())
+    }
+
+    pub fn self_check_specials(&self) -> Result<(), Error> {
+        for special in self.special_tokens() {
+            let tokenized = self.tokenize(special.clone())?;
+            if self.strip_sentinels(&tokenized) != [special.clone()] {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Special token {special:?} does not survive tokenization"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn rebuild_word_map(&mut self, corpus: &str) {
+        let corpus = self.normalize(corpus);
+        let words = self.config.pre_tokenizer.pre_tokenize(&corpus);
+
+        self.tokenizer = self.word_map(words);
+        self.clear_cache();
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut alphabet = self
+            .alphabet
+            .iter()
+            .chain(other.alphabet.iter())
+            .cloned()
+            .collect::<Vec<String>>();
+        alphabet.sort();
+        alphabet.dedup();
+
+        // A merge learned by both sides is placed at the earlier of its two
+        // positions; merges landing on the same position keep `self` first.
+        let mut ranks = HashMap::<&Merge, (usize, usize)>::new();
+        for (source, merges) in [&self.merges, &other.merges].into_iter().enumerate() {
+            for (position, merge) in merges.iter().enumerate() {
+                let rank = ranks.entry(merge).or_insert((position, source));
+                *rank = (*rank).min((position, source));
+            }
+        }
+        let mut ranked = ranks.into_iter().collect::<Vec<(&Merge, (usize, usize))>>();
+        ranked.sort_by(|(a, a_rank), (b, b_rank)| a_rank.cmp(b_rank).then(a.cmp(b)));
+        let merges = ranked
+            .into_iter()
+            .map(|(merge, _)| merge.clone())
+            .collect::<Vec<Merge>>();
+
+        let mut special_tokens = self.config.special_tokens.clone();
+        for token in other.config.special_tokens.iter() {
+            if !special_tokens.contains(token) {
+                special_tokens.push(token.clone());
+            }
+        }
+
+        let mut frequencies = other.frequencies.clone();
+        frequencies.extend(self.frequencies.clone());
+        let mut scores = other.scores.clone();
+        scores.extend(self.scores.clone());
+        let mut joined_tokens = self.joined_tokens.clone();
+        for token in other.joined_tokens.iter() {
+            if !joined_tokens.contains(token) {
+                joined_tokens.push(token.clone());
+            }
+        }
+
+        let mut union = BytePairEncoding {
+            vocab_size: alphabet.len() + merges.len() + joined_tokens.len(),
+            tokenizer: HashMap::new(),
+            merges,
+            alphabet,
+            config: TrainingConfig {
+                special_tokens,
+                required_words: vec![],
+                ..self.config.clone()
+            },
+            frozen: false,
+            frequencies,
+            scores,
+            training_status: None,
+            joined_tokens,
+            appended_specials: 0,
+            imported_vocab: vec![],
+            rules: self.rules.clone(),
+            id_tokens: vec![],
+            token_ids: HashMap::new(),
+            merge_lookup: HashMap::new(),
+            cache: ReplayCache::default(),
+        };
+        let words = self
+            .tokenizer
+            .keys()
+            .chain(other.tokenizer.keys())
+            .cloned()
+            .collect::<Vec<String>>();
+        union.index_merges();
+        union.tokenizer = union.word_map(words);
+
+        let mut known = union.build_vocab().into_iter().collect::<HashSet<String>>();
+        for word in self
+            .config
+            .required_words
+            .iter()
+            .chain(other.config.required_words.iter())
+        {
+            if !union.config.required_words.contains(word) {
+                union.config.required_words.push(word.clone());
+                union.tokenizer.insert(word.clone(), vec![word.clone()]);
+            }
+            if known.insert(word.clone()) {
+                union.vocab_size += 1;
+            }
+        }
+        union.index_vocab();
+
+        union
+    }
+
+    fn word_map(&self, words: Vec<String>) -> HashMap<String, Vec<String>> {
+        let mut tokenizer = Self::PUNCTUATION
+            .iter()
+            .map(|c| (c.to_string(), vec![c.to_string()]))
+            .collect::<HashMap<String, Vec<String>>>();
+        for word in words {
+            if tokenizer.contains_key(&word) {
+                continue;
+            }
+
+            let mut pieces = word.chars().map(|c| c.to_string()).collect::<Vec<String>>();
+            if self.config.mark_continuations {
+                pieces = Self::mark_pieces(pieces);
+            }
+            if !pieces
+                .iter()
+                .all(|piece| self.alphabet.binary_search(piece).is_ok())
+            {
+                continue;
+            }
+
+            let merged = self.merge_pieces(pieces, &mut vec![]);
+            tokenizer.insert(word, merged);
+        }
+
+        tokenizer
+    }
+
+    pub fn to_inference_model(&self) -> InferenceModel {
+        let mut bpe = BytePairEncoding {
+            vocab_size: self.vocab_size,
+            tokenizer: HashMap::new(),
+            merges: self.merges.clone(),
+            alphabet: self.alphabet.clone(),
+            config: TrainingConfig {
+                unknown_word: UnknownWord::Replay,
+                ..self.config.clone()
+            },
+            frozen: self.frozen,
+            frequencies: HashMap::new(),
+            scores: HashMap::new(),
+            training_status: None,
+            joined_tokens: self.joined_tokens.clone(),
+            appended_specials: self.appended_specials,
+            imported_vocab: self.imported_vocab.clone(),
+            rules: self.rules.clone(),
+            id_tokens: self.id_tokens.clone(),
+            token_ids: self.token_ids.clone(),
+            merge_lookup: self.merge_lookup.clone(),
+            cache: ReplayCache::default(),
+        };
+        bpe.tokenizer = bpe.word_map(vec![]);
+
+        InferenceModel { bpe }
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.cache.hits.load(AtomicOrdering::Relaxed),
+            misses: self.cache.misses.load(AtomicOrdering::Relaxed),
+            entries: self.cache.entries.load(AtomicOrdering::Relaxed),
+        }
+    }
+
+    pub fn nearest_known_word(&self, word: &str) -> Option<&str> {
+        let mut nearest = None;
+        for candidate in self.tokenizer.keys() {
+            let distance = Self::edit_distance(word, candidate);
+            if self
+                .config
+                .max_edit_distance
+                .is_some_and(|max| distance > max)
+            {
+                continue;
+            }
+
+            nearest = match nearest {
+                Some((best, best_distance)) if (best_distance, best) <= (distance, candidate) => {
+                    Some((best, best_distance))
+                }
+                _ => Some((candidate, distance)),
+            };
+        }
+
+        nearest.map(|(word, _)| word.as_str())
+    }
+
+    fn edit_distance(left: &str, right: &str) -> usize {
+        let right = right.chars().collect::<Vec<char>>();
+        let mut previous = (0..=right.len()).collect::<Vec<usize>>();
+
+        for (i, l) in left.chars().enumerate() {
+            let mut current = vec![i + 1];
+            for (j, r) in right.iter().enumerate() {
+                let substitution = previous[j] + usize::from(l != *r);
+                current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+            }
+            previous = current;
+        }
+
+        previous[right.len()]
+    }
+
+    pub fn words_containing(&self, token: &str) -> Vec<String> {
+        let mut words = self
+            .tokenizer
+            .iter()
+            .filter(|(_, tokens)| tokens.iter().any(|candidate| candidate == token))
+            .map(|(word, _)| word.clone())
+            .collect::<Vec<String>>();
+        words.sort();
+
+        words
+    }
+
+    pub fn canonical_merge(&self, token: &str) -> Option<&Merge> {
+        self.canonical_position(token)
+            .map(|position| &self.merges[position])
+    }
+
+    pub fn merge_path(&self, token: &str) -> Vec<Merge> {
+        let mut positions = HashSet::<usize>::new();
+        let mut pending = vec![token];
+        while let Some(token) = pending.pop() {
+            let Some(position) = self.canonical_position(token) else {
+                continue;
+            };
+
+            if positions.insert(position) {
+                let (left, right) = &self.merges[position];
+                pending.push(left);
+                pending.push(right);
+            }
+        }
+
+        let mut positions = positions.into_iter().collect::<Vec<usize>>();
+        positions.sort();
+        positions
+            .into_iter()
+            .map(|position| self.merges[position].clone())
+            .collect()
+    }
+
+    fn canonical_position(&self, token: &str) -> Option<usize> {
+        self.merges
+            .iter()
+            .position(|(left, right)| self.join_pair(left, right) == token)
+    }
+
+    pub fn special_tokens(&self) -> Vec<String> {
+        let mut special_tokens = self.sentinels();
+        if self.config.unknown_character == UnknownCharacter::Unk {
+            special_tokens.push(Self::UNK_TOKEN.to_string());
+        }
+        special_tokens.extend(self.config.special_tokens.iter().cloned());
+
+        special_tokens
+    }
+
+    pub fn is_special(&self, token: &str) -> bool {
+        self.start_token().is_some_and(|start| token == start)
+            || self.end_token().is_some_and(|end| token == end)
+            || (self.config.unknown_character == UnknownCharacter::Unk && token == Self::UNK_TOKEN)
+            || self
+                .config
+                .special_tokens
+                .iter()
+                .any(|special| special == token)
+    }
+
+    fn start_token(&self) -> Option<&'static str> {
+        (!self.config.skip_special_tokens).then_some(Self::START_TOKEN)
+    }
+
+    fn end_token(&self) -> Option<&'static str> {
+        (!self.config.skip_special_tokens).then_some(Self::END_TOKEN)
+    }
+
+    fn sentinels(&self) -> Vec<String> {
+        self.start_token()
+            .into_iter()
+            .chain(self.end_token())
+            .map(String::from)
+            .collect()
+    }
+
+    fn strip_sentinels<'a, T>(&self, tokens: &'a [T]) -> &'a [T] {
+        let sentinels = usize::from(!self.config.skip_special_tokens);
+        &tokens[sentinels..tokens.len() - sentinels]
+    }
+
+    pub fn vocab(&self) -> Vec<String> {
+        self.id_tokens.clone()
+    }
+
+    fn index_vocab(&mut self) {
+        self.id_tokens = self.build_vocab();
+        self.token_ids = self
+            .id_tokens
+            .iter()
+            .enumerate()
+            .map(|(position, token)| (token.clone(), position + self.config.reserved_ids))
+            .collect();
+        self.index_merges();
+    }
+
+    fn index_merges(&mut self) {
+        self.merge_lookup = HashMap::new();
+        for (left, right) in self.merges.iter() {
+            self.merge_lookup
+                .entry(left.clone())
+                .or_default()
+                .insert(right.clone());
+        }
+    }
+
+    fn build_vocab(&self) -> Vec<String> {
+        // Imported ids are kept as given, and only tokens the imported
+        // vocabulary lacks get ids after it.
+        if !self.imported_vocab.is_empty() {
+            let mut vocab = self.imported_vocab.clone();
+            let mut seen = vocab.iter().cloned().collect::<HashSet<String>>();
+            for token in self.derived_vocab() {
+                if seen.insert(token.clone()) {
+                    vocab.push(token);
+                }
+            }
+            return vocab;
+        }
+
+        self.derived_vocab()
+    }
+
+    fn derived_vocab(&self) -> Vec<String> {
+        // Specials added after training take the ids after everything
+        // else, so no existing token changes its id.
+        let (trained, appended) = self.config.special_tokens.split_at(
+            self.config
+                .special_tokens
+                .len()
+                .saturating_sub(self.appended_specials),
+        );
+        let mut specials = vec![];
+        if self.config.unknown_character == UnknownCharacter::Unk {
+            specials.push(Self::UNK_TOKEN.to_string());
+        }
+        specials.extend(trained.iter().cloned());
+
+        let mut vocab = if self.config.special_ids_after_vocab {
+            specials.extend(self.sentinels());
+            let mut vocab = self.learned_vocab(&specials);
+            vocab.extend(specials);
+            vocab
+        } else {
+            let mut vocab = self.sentinels();
+            vocab.extend(specials);
+            vocab.extend(self.learned_vocab(&vocab));
+            vocab
+        };
+        for token in appended {
+            if !vocab.contains(token) {
+                vocab.push(token.clone());
+            }
+        }
+
+        vocab
+    }
+
+    fn learned_vocab(&self, specials: &[String]) -> Vec<String> {
+        let mut vocab = self.alphabet.clone();
+
+        let mut seen = specials
+            .iter()
+            .chain(self.alphabet.iter())
+            .cloned()
+            .collect::<HashSet<String>>();
+        for (left, right) in self.merges.iter() {
+            let merged = self.join_pair(left, right);
+            if seen.insert(merged.clone()) {
+                vocab.push(merged);
+            }
+        }
+        for word in self
+            .config
+            .required_words
+            .iter()
+            .chain(self.joined_tokens.iter())
+        {
+            if seen.insert(word.clone()) {
+                vocab.push(word.clone());
+            }
+        }
+
+        vocab
+    }
+
+    pub fn longest_token(&self) -> Option<(String, usize)> {
+        self.learned_vocab(&[])
+            .into_iter()
+            .map(|token| {
+                let length = Self::strip_continuation(&token, self.config.mark_continuations)
+                    .chars()
+                    .count();
+                (token, length)
+            })
+            .fold(None, |longest, (token, length)| match longest {
+                Some((_, longest_length)) if longest_length >= length => longest,
+                _ => Some((token, length)),
+            })
+    }
+
+    pub fn reserved_ids(&self) -> Range<usize> {
+        0..self.config.reserved_ids
+    }
+
+    pub fn token_to_id(&self, token: &str) -> Option<usize> {
+        self.token_ids.get(token).copied()
+    }
+
+    pub fn id_to_token(&self, id: usize) -> Option<String> {
+        id.checked_sub(self.config.reserved_ids)
+            .and_then(|position| self.id_tokens.get(position).cloned())
+    }
+
+    pub fn encode(&self, text: String) -> Result<Vec<usize>, Error> {
+        self.tokenize_raw(text)?
+            .iter()
+            .map(|token| self.lookup_id(token))
+            .collect()
+    }
+
+    pub fn encode_u32(&self, text: String) -> Result<Vec<u32>, Error> {
+        self.encode(text)?
+            .into_iter()
+            .map(|id| {
+                u32::try_from(id).map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Token id {id} does not fit in a u32"),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn encode_batch(&self, texts: Vec<String>) -> Vec<Result<Vec<usize>, Error>> {
+        use rayon::prelude::*;
+
+        texts
+            .into_par_iter()
+            .map(|text| self.encode(text))
+            .collect()
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    pub fn encode_batch(&self, texts: Vec<String>) -> Vec<Result<Vec<usize>, Error>> {
+        texts.into_iter().map(|text| self.encode(text)).collect()
+    }
+
+    pub fn encode_padded(
+        &self,
+        text: String,
+        length: usize,
+        pad_id: usize,
+        side: PaddingSide,
+    ) -> Result<(Vec<usize>, Vec<u8>), Error> {
+        let encoded = self.encode(text)?;
+        if encoded.len() > length {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Input of {} tokens exceeds the padded length {length}",
+                    encoded.len()
+                ),
+            ));
+        }
+
+        let padding = length - encoded.len();
+        let mut ids = vec![pad_id; padding];
+        let mut mask = vec![0; padding];
+        match side {
+            PaddingSide::Left => {
+                ids.extend(encoded);
+                mask.resize(length, 1);
+            }
+            PaddingSide::Right => {
+                ids.splice(0..0, encoded);
+                mask.splice(0..0, iter::repeat_n(1, length - padding));
+            }
+        }
+
+        Ok((ids, mask))
+    }
+
+    pub fn encode_with_tokens(&self, text: String) -> Result<(Vec<String>, Vec<usize>), Error> {
+        let mut tokens = vec![];
+        let mut encoded = vec![];
+        for token in self.tokenize_raw(text)? {
+            encoded.push(self.lookup_id(&token)?);
+            match Self::render_leading_space(&token) {
+                Some(rendered) if self.config.render_leading_space => tokens.push(rendered),
+                _ => tokens.push(token),
+            }
+        }
+
+        Ok((tokens, encoded))
+    }
+
+    fn lookup_id(&self, token: &str) -> Result<usize, Error> {
+        self.token_to_id(token).ok_or(Error::new(
+            ErrorKind::InvalidData,
+            format!("Token {token:?} has no id"),
+        ))
+    }
+
+    pub fn tokenize(&self, text: String) -> Result<Vec<String>, Error> {
+        Ok(self.render(self.tokenize_raw(text)?))
+    }
+
+    fn compile_rules(rules: &[(String, String)]) -> Result<Vec<(Regex, Regex)>, Error> {
+        let compile = |pattern: &str| {
+            Regex::new(&format!("^(?:{pattern})$")).map_err(|error| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid post-merge rule {pattern:?}: {error}"),
+                )
+            })
+        };
+
+        rules
+            .iter()
+            .map(|(left, right)| Ok((compile(left)?, compile(right)?)))
+            .collect()
+    }
+
+    fn apply_post_merge_rules<'a>(&self, tokens: Cow<'a, [String]>) -> Cow<'a, [String]> {
+        match Self::join_by_rules(&self.rules, &tokens, |token| {
+            self.token_ids.contains_key(token)
+        }) {
+            Some(joined) => Cow::Owned(joined),
+            None => tokens,
+        }
+    }
+
+    // Joins adjacent tokens of one word matched by a rule, as long as the
+    // joined token is `joinable`. Returns `None` when nothing was joined.
+    fn join_by_rules(
+        rules: &[(Regex, Regex)],
+        tokens: &[String],
+        joinable: impl Fn(&str) -> bool,
+    ) -> Option<Vec<String>> {
+        if rules.is_empty() {
+            return None;
+        }
+
+        let mut tokens = tokens.to_vec();
+        let mut joined = false;
+        let mut i = 0;
+        while i + 1 < tokens.len() {
+            let (left, right) = (&tokens[i], &tokens[i + 1]);
+            let joins = rules.iter().any(|(left_rule, right_rule)| {
+                left_rule.is_match(left) && right_rule.is_match(right)
+            }) && joinable(&format!("{left}{right}"));
+            if joins {
+                let right = tokens.remove(i + 1);
+                tokens[i].push_str(&right);
+                joined = true;
+            } else {
+                i += 1;
+            }
+        }
+
+        joined.then_some(tokens)
+    }
+
+    pub fn tokenize_map<F: Fn(String) -> String>(
+        &self,
+        text: String,
+        f: F,
+    ) -> Result<Vec<String>, Error> {
+        Ok(self.tokenize(text)?.into_iter().map(f).collect())
+    }
+
+    fn render(&self, tokens: Vec<String>) -> Vec<String> {
+        if !self.config.render_leading_space {
+            return tokens;
+        }
+
+        tokens
+            .into_iter()
+            .map(|token| Self::render_leading_space(&token).unwrap_or(token))
+            .collect()
+    }
+
+    fn render_leading_space(token: &str) -> Option<String> {
+        token
+            .strip_prefix(' ')
+            .map(|rest| format!("{}{rest}", Self::LEADING_SPACE))
+    }
+
+    fn tokenize_raw(&self, text: String) -> Result<Vec<String>, Error> {
+        let mut tokenized = Vec::from_iter(self.start_token().map(String::from));
+        for (_, tokens) in self.tokenize_words(&text)? {
+            tokenized.extend(tokens.iter().cloned());
+        }
+
+        tokenized.extend(self.end_token().map(String::from));
+        Ok(tokenized)
+    }
+
+    fn tokenize_words(&self, text: &str) -> Result<WordTokens<'_>, Error> {
+        self.tokenize_pre_tokens(self.pre_tokens(text), |word| self.tokenize_word(word))
+    }
+
+    fn pre_tokens(&self, text: &str) -> Vec<PreToken> {
+        let mut pre_tokens = vec![];
+        let mut blank = true;
+
+        let special_tokens = self.special_tokens();
+        for (position, segment) in
+            Self::split_atomic(text, &special_tokens, self.config.case_insensitive_specials)
+                .into_iter()
+                .enumerate()
+        {
+            let text = match segment {
+                Segment::Atomic(token) => {
+                    pre_tokens.push(PreToken::Atomic(token.to_string()));
+                    blank = false;
+                    continue;
+                }
+                Segment::Text(text) if position == 0 => self.normalize(text),
+                Segment::Text(text) => self.config.normalizer.normalize(text),
+            };
+            if text.is_empty() {
+                continue;
+            }
+            blank &= text.trim().is_empty();
+
+            for word in self.config.pre_tokenizer.pre_tokenize(&text) {
+                let required = word.trim_start();
+                if !self.config.required_words.iter().any(|w| w == required) {
+                    pre_tokens.push(PreToken::Word(word));
+                    continue;
+                }
+
+                let (prefix, required) = word.split_at(word.len() - required.len());
+                if !prefix.is_empty() {
+                    pre_tokens.push(PreToken::Word(prefix.to_string()));
+                }
+                pre_tokens.push(PreToken::Atomic(required.to_string()));
+            }
+        }
+
+        // Input with nothing but whitespace outside of specials tokenizes to
+        // just the sentinels.
+        if blank {
+            pre_tokens.clear();
+        }
+        pre_tokens
+    }
+
+    fn tokenize_pre_tokens<'a>(
+        &'a self,
+        pre_tokens: Vec<PreToken>,
+        mut tokenize_word: impl FnMut(&str) -> Result<Cow<'a, [String]>, Error>,
+    ) -> Result<WordTokens<'a>, Error> {
+        let mut words = vec![];
+        let mut used = usize::from(self.start_token().is_some());
+        for pre_token in pre_tokens {
+            let (word, tokens) = match pre_token {
+                PreToken::Atomic(token) => (token.clone(), Cow::Owned(vec![token])),
+                PreToken::Word(word) => {
+                    let tokens = self.apply_post_merge_rules(tokenize_word(&word)?);
+                    (word, tokens)
+                }
+            };
+            used += tokens.len();
+            words.push((word, tokens));
+
+            if self.enforce_token_limit(&mut words, used)? {
+                break;
+            }
+        }
+
+        Ok(words)
+    }
+
+    fn check_token_limit(config: &TrainingConfig) -> Result<(), Error> {
+        let sentinels = 2 * usize::from(!config.skip_special_tokens);
+        match config.max_tokens_per_input {
+            Some(max) if max < sentinels => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("max_tokens_per_input {max} cannot fit the {sentinels} sentinel tokens"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn enforce_token_limit(&self, words: &mut WordTokens<'_>, used: usize) -> Result<bool, Error> {
+        let reserved = usize::from(self.end_token().is_some());
+        let Some(limit) = self.config.max_tokens_per_input.map(|max| max - reserved) else {
+            return Ok(false);
+        };
+        if used <= limit {
+            return Ok(false);
+        }
+
+        if self.config.token_overflow == TokenOverflow::Error {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Input exceeds the limit of {} tokens", limit + reserved),
+            ));
+        }
+
+        if let Some((_, tokens)) = words.last_mut() {
+            let kept = tokens.len().saturating_sub(used - limit);
+            tokens.to_mut().truncate(kept);
+            if kept == 0 {
+                words.pop();
+            }
+        }
+        Ok(true)
+    }
+
+    fn split_atomic<'a>(
+        text: &'a str,
+        atomic_tokens: &'a [String],
+        case_insensitive: bool,
+    ) -> Vec<Segment<'a>> {
+        let mut atomic_tokens = atomic_tokens.iter().collect::<Vec<&String>>();
+        atomic_tokens.sort_by_key(|token| Reverse(token.len()));
+
+        let mut segments = vec![];
+        let (mut start, mut i) = (0, 0);
+        while let Some(c) = text[i..].chars().next() {
+            let matched = atomic_tokens.iter().find(|token| {
+                text.get(i..i + token.len()).is_some_and(|candidate| {
+                    candidate == token.as_str()
+                        || (case_insensitive && candidate.eq_ignore_ascii_case(token))
+                })
+            });
+
+            match matched {
+                Some(token) => {
+                    if start < i {
+                        segments.push(Segment::Text(&text[start..i]));
+                    }
+                    segments.push(Segment::Atomic(token.as_str()));
+                    i += token.len();
+                    start = i;
+                }
+                None => i += c.len_utf8(),
+            }
+        }
+        if start < text.len() {
+            segments.push(Segment::Text(&text[start..]));
+        }
+
+        segments
+    }
+
+    pub fn check_against(&self, cases: &[(String, Vec<String>)]) -> Vec<Mismatch> {
+        cases
+            .iter()
+            .filter_map(|(input, expected)| {
+                let actual = self.tokenize(input.clone()).ok();
+                (actual.as_ref() != Some(expected)).then(|| Mismatch {
+                    input: input.clone(),
+                    expected: expected.clone(),
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    pub fn sample_tokenize(
+        &self,
+        text: String,
+        alpha: f64,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<String>, Error> {
+        let mut tokenized = Vec::from_iter(self.start_token().map(String::from));
+
+        let words = self.tokenize_pre_tokens(self.pre_tokens(&text), |word| {
+            match self.sample_segmentation(word, alpha, rng) {
+                Some(tokens) => Ok(Cow::Owned(tokens)),
+                None => self.tokenize_word(word),
+            }
+        })?;
+        for (_, tokens) in words {
+            tokenized.extend(tokens.iter().cloned());
+        }
+
+        tokenized.extend(self.end_token().map(String::from));
+        Ok(self.render(tokenized))
+    }
+
+    fn sample_segmentation(
+        &self,
+        word: &str,
+        alpha: f64,
+        rng: &mut impl Rng,
+    ) -> Option<Vec<String>> {
+        let boundaries = word
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(iter::once(word.len()))
+            .collect::<Vec<usize>>();
+        let n = boundaries.len() - 1;
+        let piece = |start: usize, end: usize| {
+            Self::mark_piece(
+                &word[..boundaries[start]],
+                &word[boundaries[start]..boundaries[end]],
+                self.config.mark_continuations,
+            )
+        };
+        let floor = self.scores.values().copied().fold(0.0, f64::min) - 1.0;
+        let score = |start: usize, end: usize| {
+            let piece = piece(start, end);
+            self.token_ids
+                .contains_key(&piece)
+                .then(|| alpha * self.token_score(&piece).unwrap_or(floor))
+        };
+
+        let mut forward = vec![f64::NEG_INFINITY; n + 1];
+        forward[0] = 0.0;
+        for end in 1..=n {
+            let candidates = (0..end)
+                .filter_map(|start| score(start, end).map(|score| forward[start] + score))
+                .collect::<Vec<f64>>();
+            forward[end] = Self::log_sum_exp(&candidates);
+        }
+        if forward[n] == f64::NEG_INFINITY {
+            return None;
+        }
+
+        let mut tokens = vec![];
+        let mut end = n;
+        while end > 0 {
+            let candidates = (0..end)
+                .filter_map(|start| score(start, end).map(|score| (start, forward[start] + score)))
+                .filter(|(_, weight)| *weight > f64::NEG_INFINITY)
+                .collect::<Vec<(usize, f64)>>();
+
+            let total = Self::log_sum_exp(&candidates.iter().map(|(_, w)| *w).collect::<Vec<_>>());
+            let mut threshold = rng.random::<f64>();
+            let mut start = candidates[candidates.len() - 1].0;
+            for (candidate, weight) in candidates.iter() {
+                threshold -= (weight - total).exp();
+                if threshold <= 0.0 {
+                    start = *candidate;
+                    break;
+                }
+            }
+
+            tokens.push(piece(start, end));
+            end = start;
+        }
+
+        tokens.reverse();
+        Some(tokens)
+    }
+
+    fn log_sum_exp(values: &[f64]) -> f64 {
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        if max == f64::NEG_INFINITY {
+            return max;
+        }
+
+        max + values
+            .iter()
+            .map(|value| (value - max).exp())
+            .sum::<f64>()
+            .ln()
+    }
+
+    pub fn tokenize_grouped(&self, text: String) -> Result<Vec<(String, Vec<String>)>, Error> {
+        let sentinel = |token: &str| (token.to_string(), vec![token.to_string()]);
+        let mut grouped = Vec::from_iter(self.start_token().map(sentinel));
+
+        for (word, tokens) in self.tokenize_words(&text)? {
+            grouped.push((word, self.render(tokens.into_owned())));
+        }
+
+        grouped.extend(self.end_token().map(sentinel));
+        Ok(grouped)
+    }
+
+    pub fn tokenize_with_word_starts(
+        &self,
+        text: String,
+    ) -> Result<(Vec<String>, Vec<usize>), Error> {
+        let grouped = self.tokenize_grouped(text)?;
+        let words = self.strip_sentinels(&grouped).len();
+        let first = usize::from(self.start_token().is_some());
+
+        let mut tokens = vec![];
+        let mut starts = vec![];
+        for (position, (_, word_tokens)) in grouped.into_iter().enumerate() {
+            if (first..first + words).contains(&position) {
+                starts.push(tokens.len());
+            }
+            tokens.extend(word_tokens);
+        }
+
+        Ok((tokens, starts))
+    }
 
-    pub fn from(corpus: String, max_vocab_size: usize) -> Self {
-        let vocabulary = Self::build_vocablary(&corpus);
-        let mut vocab_size = vocabulary.len() - 2;
+    pub fn tokenize_until(&self, text: String, stop: &str) -> Result<Vec<String>, Error> {
+        let mut tokenized = Vec::from_iter(self.start_token().map(String::from));
 
-        assert!(
-            max_vocab_size > vocab_size,
-            "vocab_size {} must be greater than the size of the text {}",
-            max_vocab_size,
-            vocab_size
-        );
-        if max_vocab_size == vocab_size {
-            return BytePairEncoding {
-                vocab_size: max_vocab_size,
-                tokenizer: HashMap::new(),
+        let mut pre_tokens = self.pre_tokens(&text);
+        let stop_at = pre_tokens.iter().position(|pre_token| {
+            let word = match pre_token {
+                PreToken::Atomic(word) | PreToken::Word(word) => word,
             };
+            word == stop || word.trim_start() == stop
+        });
+        if let Some(stop_at) = stop_at {
+            pre_tokens.truncate(stop_at);
         }
 
-        let pre_tokenized = Self::pre_tokenize(&corpus);
-        let mut words = Self::text_to_map(&pre_tokenized);
+        let words = self.tokenize_pre_tokens(pre_tokens, |word| self.tokenize_word(word))?;
+        for (_, tokens) in words {
+            tokenized.extend(tokens.iter().cloned());
+        }
 
-        while max_vocab_size > vocab_size {
-            let (pair, freq) = Self::get_most_frequent_pair(&words);
-            if freq == 0 {
+        if stop_at.is_none() {
+            tokenized.extend(self.end_token().map(String::from));
+        }
+        Ok(self.render(tokenized))
+    }
+
+    pub fn tokenize_borrowed(&self, text: String) -> Result<Vec<Cow<'_, str>>, Error> {
+        let mut tokenized = Vec::from_iter(self.start_token().map(Cow::Borrowed));
+
+        for (_, tokens) in self.tokenize_words(&text)? {
+            match tokens {
+                Cow::Borrowed(tokens) => {
+                    tokenized.extend(tokens.iter().map(|token| Cow::Borrowed(token.as_str())))
+                }
+                Cow::Owned(tokens) => tokenized.extend(tokens.into_iter().map(Cow::Owned)),
+            }
+        }
+        if self.config.render_leading_space {
+            for token in tokenized.iter_mut() {
+                if let Some(rendered) = Self::render_leading_space(token) {
+                    *token = Cow::Owned(rendered);
+                }
+            }
+        }
+
+        tokenized.extend(self.end_token().map(Cow::Borrowed));
+        Ok(tokenized)
+    }
+
+    pub fn tokenize_bytes(&self, bytes: &[u8]) -> Result<Vec<String>, Error> {
+        self.tokenize(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    pub fn tokenize_truncated(
+        &self,
+        text: String,
+        max_tokens: usize,
+    ) -> Result<(Vec<String>, bool), Error> {
+        let sentinels = self.sentinels().len();
+        if max_tokens < sentinels {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("max_tokens {max_tokens} cannot fit the {sentinels} sentinel tokens"),
+            ));
+        }
+
+        let mut tokenized = self.tokenize(text)?;
+        let reserved = usize::from(self.end_token().is_some());
+        tokenized.truncate(tokenized.len() - reserved);
+
+        let budget = max_tokens - reserved;
+        let truncated = tokenized.len() > budget;
+        tokenized.truncate(budget);
+        tokenized.extend(self.end_token().map(String::from));
+
+        Ok((tokenized, truncated))
+    }
+
+    pub fn tokenize_with_budget(
+        &self,
+        text: String,
+        max_bytes: usize,
+    ) -> Result<(Vec<String>, bool), Error> {
+        let mut tokenized = self.tokenize(text)?;
+        let sentinels = usize::from(!self.config.skip_special_tokens);
+        tokenized.truncate(tokenized.len() - sentinels);
+
+        let mut used = 0;
+        let mut kept = sentinels;
+        for token in tokenized[sentinels..].iter() {
+            let token = Self::strip_continuation(token, self.config.mark_continuations);
+            let length = match Self::parse_byte_token(token) {
+                Some(_) => 1,
+                None if self.config.render_leading_space
+                    && token.starts_with(Self::LEADING_SPACE) =>
+                {
+                    token.len() - Self::LEADING_SPACE.len_utf8() + 1
+                }
+                None => token.len(),
+            };
+            if used + length > max_bytes {
                 break;
             }
 
-            words = Self::merge_by_pair(words, pair);
-            vocab_size += 1;
+            used += length;
+            kept += 1;
         }
 
-        let tokenizer_mapper =
-            words
-                .into_keys()
-                .fold(HashMap::<String, Vec<String>>::new(), |mut map, word| {
-                    map.insert(word.join(""), word);
-                    map
-                });
+        let truncated = tokenized.len() > kept;
+        tokenized.truncate(kept);
+        tokenized.extend(self.end_token().map(String::from));
 
-        BytePairEncoding {
-            vocab_size,
-            tokenizer: tokenizer_mapper,
+        Ok((tokenized, truncated))
+    }
+
+    pub fn encode_windows(
+        &self,
+        text: String,
+        window: usize,
+        stride: usize,
+    ) -> Result<Vec<Vec<String>>, Error> {
+        if window == 0 || stride == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "window and stride must be greater than zero",
+            ));
+        }
+        if stride > window {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("stride {stride} exceeds window {window} and would skip tokens"),
+            ));
+        }
+
+        let tokenized = self.tokenize(text)?;
+        let tokens = self.strip_sentinels(&tokenized);
+
+        let mut windows = vec![];
+        let mut start = 0;
+        loop {
+            let end = (start + window).min(tokens.len());
+
+            let mut windowed = Vec::from_iter(self.start_token().map(String::from));
+            windowed.extend(tokens[start..end].iter().cloned());
+            windowed.extend(self.end_token().map(String::from));
+            windows.push(windowed);
+
+            if end == tokens.len() {
+                break;
+            }
+            start += stride;
         }
+
+        Ok(windows)
     }
 
-    pub fn tokenize(&self, text: String) -> Result<Vec<String>, Error> {
-        let mut tokenized = vec![Self::START_TOKEN.to_string()];
+    pub fn tokenize_with_special_mask(
+        &self,
+        text: String,
+    ) -> Result<(Vec<String>, Vec<bool>), Error> {
+        let tokenized = self.tokenize(text)?;
+        let special_mask = tokenized
+            .iter()
+            .map(|token| self.is_special(token))
+            .collect::<Vec<bool>>();
 
-        let pre_tokenized = Self::pre_tokenize(&text);
-        for word in pre_tokenized.into_iter() {
-            let tokenized_word = self.tokenizer.get(&word).ok_or(Error::new(
-                ErrorKind::InvalidInput,
-                "Word not found in vocabulary",
-            ))?;
-            tokenized.extend(tokenized_word.clone());
+        Ok((tokenized, special_mask))
+    }
+
+    pub fn count_tokens(&self, text: String) -> Result<usize, Error> {
+        let words = self.tokenize_words(&text)?;
+
+        Ok(words
+            .iter()
+            .fold(self.sentinels().len(), |count, (_, tokens)| {
+                count + tokens.len()
+            }))
+    }
+
+    fn normalize(&self, text: &str) -> String {
+        let text = self.config.normalizer.normalize(text);
+        if self.config.add_prefix_space {
+            return Self::with_prefix_space(text);
         }
 
-        tokenized.push(Self::END_TOKEN.to_string());
-        Ok(tokenized)
+        text
+    }
+
+    fn trim_corpus(corpus: &str, trim_start: bool, trim_end: bool) -> String {
+        let mut corpus = corpus
+            .split_inclusive('\n')
+            .filter(|line| !(line.ends_with('\n') && line.trim().is_empty()))
+            .collect::<String>();
+
+        if trim_end {
+            corpus.truncate(corpus.trim_end().len());
+        }
+        if trim_start {
+            corpus = corpus.trim_start().to_string();
+        }
+
+        corpus
+    }
+
+    fn with_prefix_space(text: String) -> String {
+        if text.is_empty() || text.starts_with(' ') {
+            return text;
+        }
+
+        format!(" {text}")
+    }
+
+    fn tokenize_word(&self, word: &str) -> Result<Cow<'_, [String]>, Error> {
+        match self.tokenizer.get(word) {
+            Some(tokens) if self.config.merge_strategy == MergeStrategy::Priority => {
+                return Ok(Cow::Borrowed(tokens));
+            }
+            Some(_) => {}
+            None if self.config.unknown_word == UnknownWord::Error => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Word not found in vocabulary",
+                ));
+            }
+            None => {}
+        }
+
+        let capacity = self.config.cache_capacity;
+        if capacity == 0 {
+            return Ok(Cow::Owned(
+                self.merge_pieces(self.split_characters(word)?, &mut vec![]),
+            ));
+        }
+        if let Some(tokens) = self.cache.get(word) {
+            return Ok(Cow::Owned(tokens));
+        }
+
+        let merged = self.merge_pieces(self.split_characters(word)?, &mut vec![]);
+        self.cache.insert(word, &merged, capacity);
+
+        Ok(Cow::Owned(merged))
+    }
+
+    pub fn trace_merges(&self, word: &str) -> Result<Vec<Merge>, Error> {
+        let mut fired = vec![];
+        self.merge_pieces(self.split_characters(word)?, &mut fired);
+
+        Ok(fired)
+    }
+
+    fn split_characters(&self, word: &str) -> Result<Vec<String>, Error> {
+        let mut pieces = vec![];
+        for (i, c) in word.char_indices() {
+            let character = c.to_string();
+            let piece = Self::mark_piece(&word[..i], &character, self.config.mark_continuations);
+            if self.alphabet.binary_search(&piece).is_ok() {
+                pieces.push(piece);
+                continue;
+            }
+            if self.alphabet.binary_search(&character).is_ok() {
+                pieces.push(character);
+                continue;
+            }
+
+            match self.config.unknown_character {
+                UnknownCharacter::Error => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Character {character:?} not found in alphabet"),
+                    ));
+                }
+                UnknownCharacter::ByteFallback => {
+                    pieces.extend(character.bytes().map(Self::byte_token));
+                }
+                UnknownCharacter::Unk => pieces.push(Self::UNK_TOKEN.to_string()),
+            }
+        }
+
+        Ok(pieces)
+    }
+
+    fn merge_pieces(&self, pieces: Vec<String>, fired: &mut Vec<Merge>) -> Vec<String> {
+        match self.config.merge_strategy {
+            MergeStrategy::Priority => self.merges.iter().fold(pieces, |pieces, merge| {
+                let before = pieces.len();
+                let pieces =
+                    Self::merge_word(pieces, &merge.0, &merge.1, self.config.mark_continuations);
+                if pieces.len() < before {
+                    fired.push(merge.clone());
+                }
+                pieces
+            }),
+            MergeStrategy::GreedyLeftToRight => self.merge_greedy(pieces, fired),
+        }
+    }
+
+    fn merge_greedy(&self, mut pieces: Vec<String>, fired: &mut Vec<Merge>) -> Vec<String> {
+        let mut i = 0;
+        while i + 1 < pieces.len() {
+            if self
+                .merge_lookup
+                .get(&pieces[i])
+                .is_some_and(|rights| rights.contains(&pieces[i + 1]))
+            {
+                let right = pieces.remove(i + 1);
+                fired.push((pieces[i].clone(), right.clone()));
+                pieces[i] = Self::join(&pieces[i], &right, self.config.mark_continuations);
+            } else {
+                i += 1;
+            }
+        }
+
+        pieces
+    }
+
+    fn byte_token(byte: u8) -> String {
+        format!("<0x{byte:02X}>")
+    }
+
+    fn parse_byte_token(token: &str) -> Option<u8> {
+        let hex = token.strip_prefix("<0x")?.strip_suffix('>')?;
+        if hex.len() != 2 {
+            return None;
+        }
+
+        u8::from_str_radix(hex, 16).ok()
+    }
+
+    pub fn render_tokens(tokens: &[String]) -> String {
+        tokens
+            .iter()
+            .map(|token| format!("⟨{}⟩", token.replace(' ', "␣").replace('\n', "↵")))
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut known = self
+            .alphabet
+            .iter()
+            .chain(self.config.required_words.iter())
+            .chain(self.joined_tokens.iter())
+            .cloned()
+            .chain(self.special_tokens())
+            .collect::<HashSet<String>>();
+
+        for (left, right) in self.merges.iter() {
+            if !known.contains(left) || !known.contains(right) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Merge ({left:?}, {right:?}) uses a token not created earlier"),
+                ));
+            }
+            known.insert(self.join_pair(left, right));
+        }
+
+        for (word, tokens) in self.tokenizer.iter() {
+            if let Some(token) = tokens.iter().find(|token| !known.contains(*token)) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Word {word:?} maps to unknown token {token:?}"),
+                ));
+            }
+            if Self::surface(tokens, self.config.mark_continuations) != *word {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Word {word:?} does not match its tokens"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn merge_diff(&self, other: &BytePairEncoding) -> (Vec<Merge>, Vec<Merge>) {
+        let (left, right) = (&self.merges, &other.merges);
+
+        let mut common = vec![vec![0; right.len() + 1]; left.len() + 1];
+        for i in (0..left.len()).rev() {
+            for j in (0..right.len()).rev() {
+                common[i][j] = if left[i] == right[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+
+        let mut added = vec![];
+        let mut removed = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() && j < right.len() {
+            if left[i] == right[j] {
+                i += 1;
+                j += 1;
+            } else if common[i + 1][j] >= common[i][j + 1] {
+                added.push(left[i].clone());
+                i += 1;
+            } else {
+                removed.push(right[j].clone());
+                j += 1;
+            }
+        }
+        added.extend(left[i..].iter().cloned());
+        removed.extend(right[j..].iter().cloned());
+
+        (added, removed)
+    }
+
+    pub fn detokenize(&self, tokens: &[String]) -> Result<String, Error> {
+        self.detokenize_with(tokens, true)
+    }
+
+    pub fn detokenize_with(&self, tokens: &[String], strict: bool) -> Result<String, Error> {
+        let start = [Self::START_TOKEN.to_string()];
+        let end = [Self::END_TOKEN.to_string()];
+
+        let content = if strict && !self.config.skip_special_tokens {
+            tokens
+                .strip_prefix(&start)
+                .and_then(|tokens| tokens.strip_suffix(&end))
+                .ok_or(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Tokens must be wrapped in start and end tokens",
+                ))?
+        } else {
+            let tokens = tokens.strip_prefix(&start).unwrap_or(tokens);
+            tokens.strip_suffix(&end).unwrap_or(tokens)
+        };
+
+        let mut text = String::new();
+        let mut bytes = vec![];
+        for token in content.iter() {
+            match Self::parse_byte_token(token) {
+                Some(byte) => bytes.push(byte),
+                None => {
+                    text.push_str(&String::from_utf8_lossy(&bytes));
+                    bytes.clear();
+                    let token = Self::strip_continuation(token, self.config.mark_continuations);
+                    match token.strip_prefix(Self::LEADING_SPACE) {
+                        Some(rest) if self.config.render_leading_space => {
+                            text.push(' ');
+                            text.push_str(rest);
+                        }
+                        _ => text.push_str(token),
+                    }
+                }
+            }
+        }
+        text.push_str(&String::from_utf8_lossy(&bytes));
+
+        // The prefix space is only added when the text lacks one, so the
+        // leading space of text that already had it is dropped as well.
+        if self.config.add_prefix_space && text.starts_with(' ') {
+            text.remove(0);
+        }
+
+        Ok(text)
+    }
+
+    pub fn token_entropy(&self, text: String) -> Result<f64, Error> {
+        let tokenized = self.tokenize(text)?;
+        let tokens = self.strip_sentinels(&tokenized);
+
+        let counts = tokens
+            .iter()
+            .fold(HashMap::<&String, usize>::new(), |mut counts, token| {
+                *counts.entry(token).or_insert(0) += 1;
+                counts
+            });
+
+        let total = tokens.len() as f64;
+        let entropy = counts
+            .into_values()
+            .map(|count| {
+                let probability = count as f64 / total;
+                -probability * probability.log2()
+            })
+            .sum::<f64>();
+
+        Ok(entropy)
+    }
+
+    pub fn token_histogram(&self, text: String) -> Result<HashMap<String, usize>, Error> {
+        let tokenized = self.tokenize(text)?;
+
+        Ok(self
+            .strip_sentinels(&tokenized)
+            .iter()
+            .fold(HashMap::new(), |mut counts, token| {
+                *counts.entry(token.clone()).or_insert(0) += 1;
+                counts
+            }))
+    }
+
+    pub fn fertility(&self, text: String) -> Result<f64, Error> {
+        let grouped = self.tokenize_grouped(text)?;
+        let words = self.strip_sentinels(&grouped);
+        if words.is_empty() {
+            return Ok(0.0);
+        }
+
+        let tokens = words.iter().map(|(_, tokens)| tokens.len()).sum::<usize>();
+        Ok(tokens as f64 / words.len() as f64)
+    }
+
+    pub fn oov_rate_over_files(&self, paths: &[PathBuf]) -> Result<f64, Error> {
+        let mut words = 0;
+        let mut unknown = 0;
+        for path in paths {
+            let text = self.normalize(&fs::read_to_string(path)?);
+            for word in self.config.pre_tokenizer.pre_tokenize(&text) {
+                words += 1;
+                if !self.tokenizer.contains_key(&word) {
+                    unknown += 1;
+                }
+            }
+        }
+        if words == 0 {
+            return Ok(0.0);
+        }
+
+        Ok(unknown as f64 / words as f64)
+    }
+
+    pub fn char_coverage(&self, text: &str) -> f64 {
+        let text = self.config.normalizer.normalize(text);
+        let total = text.chars().count();
+        if total == 0 {
+            return 1.0;
+        }
+
+        let covered = text
+            .chars()
+            .filter(|c| {
+                let character = c.to_string();
+                let marked = format!("{}{c}", Self::CONTINUATION_MARKER);
+                self.alphabet.binary_search(&character).is_ok()
+                    || (self.config.mark_continuations
+                        && self.alphabet.binary_search(&marked).is_ok())
+            })
+            .count();
+
+        covered as f64 / total as f64
     }
 
-    fn build_vocablary(corpus: &str) -> Vec<String> {
+    fn build_vocablary(corpus: &str, initial_alphabet: &[String]) -> Vec<String> {
         let alphabet = corpus
             .chars()
             .map(|c| c.to_string())
+            .chain(Self::PUNCTUATION.iter().map(|c| c.to_string()))
+            .chain(initial_alphabet.iter().cloned())
             .collect::<HashSet<String>>();
 
         let mut vocabulary = alphabet.into_iter().collect::<Vec<String>>();
@@ -85,85 +2603,154 @@ impl BytePairEncoding {
         vocabulary
     }
 
-    fn pre_tokenize(corpus: &str) -> Vec<String> {
-        let mut prepped = vec![];
-        let mut word = vec![];
+    fn into_alphabet(vocabulary: impl IntoIterator<Item = String>) -> Vec<String> {
+        let mut alphabet = vocabulary
+            .into_iter()
+            .filter(|token| token != Self::START_TOKEN && token != Self::END_TOKEN)
+            .collect::<Vec<String>>();
+        alphabet.sort();
+        alphabet.dedup();
 
-        for c in corpus.chars() {
-            if !word.is_empty() && Self::PUNCTUATION.contains(&c) {
-                prepped.push(word.join(""));
-                word = vec![];
-            }
-
-            word.push(c.to_string());
-        }
-        prepped.push(word.join(""));
-
-        prepped
+        alphabet
     }
 
-    fn text_to_map(text: &[String]) -> HashMap<Vec<String>, usize> {
-        text.iter()
-            .fold(HashMap::<Vec<String>, usize>::new(), |mut words, word| {
+    fn text_to_map(text: &[String], capacity: usize) -> Map<Vec<String>, usize> {
+        text.iter().fold(
+            Map::<Vec<String>, usize>::with_capacity_and_hasher(capacity, Default::default()),
+            |mut words, word| {
+                if word.is_empty() {
+                    return words;
+                }
+
                 let splitted_word = word.chars().map(|c| c.to_string()).collect::<Vec<String>>();
 
                 *words.entry(splitted_word).or_insert(0) += 1;
                 words
-            })
+            },
+        )
+    }
+
+    fn analyze(corpus: &str) -> CorpusStats {
+        let characters = corpus.chars().collect::<HashSet<char>>();
+        let pre_tokenized = PreTokenizer::default().pre_tokenize(corpus);
+        let words = Self::text_to_map(&pre_tokenized, 0);
+
+        let mut bigrams = Map::<Merge, usize>::default();
+        for (word, freq) in words.iter() {
+            for pair in word.windows(2) {
+                *bigrams
+                    .entry((pair[0].clone(), pair[1].clone()))
+                    .or_insert(0) += freq;
+            }
+        }
+
+        let mut top_bigrams = bigrams.into_iter().collect::<Vec<(Merge, usize)>>();
+        top_bigrams.sort_by(|(a, a_freq), (b, b_freq)| b_freq.cmp(a_freq).then(a.cmp(b)));
+        top_bigrams.truncate(Self::TOP_BIGRAMS);
+
+        CorpusStats {
+            unique_characters: characters.len(),
+            unique_words: words.len(),
+            total_words: pre_tokenized.len(),
+            top_bigrams,
+        }
     }
 
-    fn get_most_frequent_pair(words: &HashMap<Vec<String>, usize>) -> (Vec<String>, usize) {
-        let mut pairs = HashMap::<Vec<String>, usize>::new();
-        let (mut most_freq_pair, mut highest_freq) = (vec![], 0);
+    fn get_most_frequent_pair(
+        words: &Map<Vec<String>, usize>,
+        isolate_scripts: bool,
+    ) -> Option<(Vec<String>, usize)> {
+        let mut pairs = Map::<Vec<String>, usize>::default();
+        let mut most_frequent: Option<(Vec<String>, usize)> = None;
 
         for (word, freq) in words.iter() {
             let n = word.len();
 
-            for i in 0..n - 1 {
+            for i in 0..n.saturating_sub(1) {
+                if isolate_scripts && !Self::same_script(&word[i], &word[i + 1]) {
+                    continue;
+                }
+                if word[i] == Self::UNK_TOKEN || word[i + 1] == Self::UNK_TOKEN {
+                    continue;
+                }
+
                 let pair = vec![word[i].clone(), word[i + 1].clone()];
                 let entry = pairs.entry(pair.clone()).or_insert(0);
                 *entry += freq;
 
-                match (*entry).cmp(&highest_freq) {
-                    Ordering::Greater => {
-                        highest_freq = *entry;
-                        most_freq_pair = pair;
-                    }
-                    Ordering::Equal => {
-                        most_freq_pair = most_freq_pair.max(pair);
-                    }
-                    _ => {}
+                let replaces = match &most_frequent {
+                    None => true,
+                    Some((most_freq_pair, highest_freq)) => match (*entry).cmp(highest_freq) {
+                        Ordering::Greater => true,
+                        Ordering::Equal => pair > *most_freq_pair,
+                        Ordering::Less => false,
+                    },
+                };
+                if replaces {
+                    most_frequent = Some((pair, *entry));
                 }
             }
         }
 
-        (most_freq_pair, highest_freq)
+        most_frequent
+    }
+
+    fn same_script(left: &str, right: &str) -> bool {
+        let script = |piece: &str| {
+            piece
+                .chars()
+                .map(|c| c.script())
+                .find(|script| !matches!(script, Script::Common | Script::Inherited))
+        };
+
+        match (script(left), script(right)) {
+            (Some(left), Some(right)) => left == right,
+            _ => true,
+        }
     }
 
     fn merge_by_pair(
-        words: HashMap<Vec<String>, usize>,
+        words: Map<Vec<String>, usize>,
         pair: Vec<String>,
-    ) -> HashMap<Vec<String>, usize> {
-        let mut new_words = HashMap::<Vec<String>, usize>::with_capacity(words.len());
-        let pair_str = pair.join("");
+        mark_continuations: bool,
+    ) -> Map<Vec<String>, usize> {
+        let mut new_words =
+            Map::<Vec<String>, usize>::with_capacity_and_hasher(words.len(), Default::default());
 
         for (word, freq) in words.into_iter() {
-            let mut new_word = word.clone();
-            let mut i = 0;
-
-            while i < new_word.len() - 1 {
-                if new_word[i] == pair[0] && new_word[i + 1] == pair[1] {
-                    new_word[i] = pair_str.clone();
-                    new_word.remove(i + 1);
-                }
-                i += 1;
-            }
-
+            let new_word = Self::merge_word(word, &pair[0], &pair[1], mark_continuations);
             *new_words.entry(new_word).or_insert(0) += freq;
         }
 
         new_words
     }
+
+    fn merge_word(
+        mut word: Vec<String>,
+        left: &str,
+        right: &str,
+        mark_continuations: bool,
+    ) -> Vec<String> {
+        let mut i = 0;
+
+        while i + 1 < word.len() {
+            if word[i] == left && word[i + 1] == right {
+                word[i] = Self::join(left, right, mark_continuations);
+                word.remove(i + 1);
+            }
+            i += 1;
+        }
+
+        word
+    }
+}
+
+impl FromStr for BytePairEncoding {
+    type Err = Error;
+
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str::<Self>(json)?.restore()
+    }
 }
 
 #[cfg(test)]
@@ -181,7 +2768,9 @@ mod tests {
     #[test]
     fn build_vocablary_returns_unique_characters() {
         let expected = vec![
+            "\n",
             " ",
+            "!",
             ",",
             ".",
             "<|endoftext|>",
@@ -193,16 +2782,31 @@ mod tests {
             "t",
             "y",
         ];
-        let mut actual = BytePairEncoding::build_vocablary(&TEXT);
+        let mut actual = BytePairEncoding::build_vocablary(TEXT, &[]);
         actual.sort();
 
         assert_eq!(expected, actual);
     }
 
     #[test]
-    fn pre_tokenize_returns_splitted_string() {
-        let expected = vec!["a", " test", "?", " yes", ",", " a", " test", "."];
-        let actual = BytePairEncoding::pre_tokenize(&TEXT);
+    fn build_vocablary_merges_initial_alphabet() {
+        let initial_alphabet = str_vec_to_string_vec(vec!["a", "z"]);
+
+        let expected = vec![
+            "\n",
+            " ",
+            "!",
+            ",",
+            ".",
+            "<|endoftext|>",
+            "<|startoftext|>",
+            "?",
+            "a",
+            "b",
+            "z",
+        ];
+        let mut actual = BytePairEncoding::build_vocablary("ab", &initial_alphabet);
+        actual.sort();
 
         assert_eq!(expected, actual);
     }
@@ -220,25 +2824,89 @@ mod tests {
         ]
         .into_iter()
         .map(|(arr, freq)| (str_vec_to_string_vec(arr), freq))
-        .collect::<HashMap<Vec<String>, usize>>();
+        .collect::<Map<Vec<String>, usize>>();
 
-        let pretokenized_text = BytePairEncoding::pre_tokenize(&TEXT);
-        let actual = BytePairEncoding::text_to_map(&pretokenized_text);
+        let pretokenized_text = PreTokenizer::default().pre_tokenize(TEXT);
+        let actual = BytePairEncoding::text_to_map(&pretokenized_text, 0);
 
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn get_most_frequent_pair_returns_the_most_frequent_pair() {
-        let expected = (vec!["e".to_string(), "s".to_string()], 3);
+        let expected = Some((vec!["e".to_string(), "s".to_string()], 3));
 
-        let pretokenized_text = BytePairEncoding::pre_tokenize(&TEXT);
-        let mapped_text = BytePairEncoding::text_to_map(&pretokenized_text);
-        let actual = BytePairEncoding::get_most_frequent_pair(&mapped_text);
+        let pretokenized_text = PreTokenizer::default().pre_tokenize(TEXT);
+        let mapped_text = BytePairEncoding::text_to_map(&pretokenized_text, 0);
+        let actual = BytePairEncoding::get_most_frequent_pair(&mapped_text, false);
 
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn get_most_frequent_pair_returns_none_without_pairs() {
+        let pretokenized_text = PreTokenizer::default().pre_tokenize(".. ,!");
+        let mapped_text = BytePairEncoding::text_to_map(&pretokenized_text, 0);
+        let actual = BytePairEncoding::get_most_frequent_pair(&mapped_text, false);
+
+        assert_eq!(None, actual);
+    }
+
+    #[test]
+    fn get_most_frequent_pair_ignores_empty_words() {
+        let mut words = Map::<Vec<String>, usize>::default();
+        words.insert(vec![], 1);
+        words.insert(vec!["a".to_string()], 1);
+
+        assert_eq!(
+            None,
+            BytePairEncoding::get_most_frequent_pair(&words, false)
+        );
+    }
+
+    #[test]
+    fn get_most_frequent_pair_selects_the_only_pair() {
+        let mut words = Map::<Vec<String>, usize>::default();
+        words.insert(vec!["a".to_string(), "b".to_string()], 1);
+
+        assert_eq!(
+            Some((vec!["a".to_string(), "b".to_string()], 1)),
+            BytePairEncoding::get_most_frequent_pair(&words, false)
+        );
+    }
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_and_substitutions() {
+        assert_eq!(0, BytePairEncoding::edit_distance("token", "token"));
+        assert_eq!(1, BytePairEncoding::edit_distance("tokn", "token"));
+        assert_eq!(3, BytePairEncoding::edit_distance("kitten", "sitting"));
+        assert_eq!(3, BytePairEncoding::edit_distance("", "abc"));
+    }
+
+    #[test]
+    fn text_to_map_skips_empty_words() {
+        let pretokenized_text = PreTokenizer::default().pre_tokenize("");
+        let actual = BytePairEncoding::text_to_map(&pretokenized_text, 0);
+
+        assert!(actual.is_empty());
+    }
+
+    #[test]
+    fn from_n_merges_on_empty_corpus_does_not_panic() {
+        let actual = BytePairEncoding::from_n_merges(String::new(), 3);
+
+        assert!(actual.merges.is_empty());
+    }
+
+    #[test]
+    fn from_n_merges_performs_exactly_n_merges() {
+        let expected = vec![("e".to_string(), "s".to_string())];
+        let actual = BytePairEncoding::from_n_merges(TEXT.to_string(), 1);
+
+        assert_eq!(expected, actual.merges);
+        assert_eq!(actual.alphabet.len() + 1, actual.vocab_size);
+    }
+
     #[test]
     fn merge_by_pair_returns_a_new_map_with_merged_words() {
         let pair = vec!["e".to_string(), "s".to_string()];
@@ -254,11 +2922,11 @@ mod tests {
         ]
         .into_iter()
         .map(|(arr, freq)| (str_vec_to_string_vec(arr), freq))
-        .collect::<HashMap<Vec<String>, usize>>();
+        .collect::<Map<Vec<String>, usize>>();
 
-        let pretokenized_text = BytePairEncoding::pre_tokenize(&TEXT);
-        let mapped_text = BytePairEncoding::text_to_map(&pretokenized_text);
-        let actual = BytePairEncoding::merge_by_pair(mapped_text, pair);
+        let pretokenized_text = PreTokenizer::default().pre_tokenize(TEXT);
+        let mapped_text = BytePairEncoding::text_to_map(&pretokenized_text, 0);
+        let actual = BytePairEncoding::merge_by_pair(mapped_text, pair, false);
 
         assert_eq!(expected, actual);
     }
@@ -0,0 +1,49 @@
+use crate::{BytePairEncoding, UnknownCharacter, UnknownWord};
+
+const MAX_MERGES: usize = 64;
+
+pub fn fuzz_train(data: &[u8]) {
+    let Some((&merges, corpus)) = data.split_first() else {
+        return;
+    };
+
+    let corpus = BytePairEncoding::builder().prepare(String::from_utf8_lossy(corpus).into_owned());
+    let max_vocab_size = corpus.min_vocab_size() + merges as usize % MAX_MERGES;
+
+    if let Ok(tokenizer) = corpus.retrain_with_vocab_size(max_vocab_size) {
+        let _ = tokenizer.validate();
+    }
+}
+
+pub fn fuzz_tokenize(data: &[u8]) {
+    let Some((&split, data)) = data.split_first() else {
+        return;
+    };
+    let (corpus, text) = data.split_at((split as usize).min(data.len()));
+    let corpus = String::from_utf8_lossy(corpus).into_owned();
+
+    for (unknown_word, unknown_character) in [
+        (UnknownWord::Error, UnknownCharacter::Error),
+        (UnknownWord::Replay, UnknownCharacter::Unk),
+        (UnknownWord::Replay, UnknownCharacter::ByteFallback),
+    ] {
+        let corpus = BytePairEncoding::builder()
+            .unknown_word(unknown_word)
+            .unknown_character(unknown_character)
+            .prepare(corpus.clone());
+        let max_vocab_size = corpus.alphabet.len() + MAX_MERGES;
+        let Ok(tokenizer) = corpus.retrain_with_vocab_size(max_vocab_size) else {
+            continue;
+        };
+
+        if let Ok(tokens) = tokenizer.tokenize_bytes(text) {
+            let _ = tokenizer.detokenize(&tokens);
+            let _ = BytePairEncoding::render_tokens(&tokens);
+        }
+        let text = String::from_utf8_lossy(text).into_owned();
+        let _ = tokenizer.count_tokens(text.clone());
+        let _ = tokenizer.encode(text.clone());
+        let _ = tokenizer.token_entropy(text.clone());
+        let _ = tokenizer.tokenize_truncated(text, split as usize);
+    }
+}
@@ -0,0 +1,38 @@
+use std::io::Error;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::BytePairEncoding;
+
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InferenceModel {
+    pub(crate) bpe: BytePairEncoding,
+}
+
+impl InferenceModel {
+    pub fn tokenize(&self, text: String) -> Result<Vec<String>, Error> {
+        self.bpe.tokenize(text)
+    }
+
+    pub fn encode(&self, text: String) -> Result<Vec<usize>, Error> {
+        self.bpe.encode(text)
+    }
+
+    pub fn detokenize(&self, tokens: &[String]) -> Result<String, Error> {
+        self.bpe.detokenize(tokens)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+impl FromStr for InferenceModel {
+    type Err = Error;
+
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        Ok(InferenceModel { bpe: json.parse()? })
+    }
+}
@@ -1,3 +1,23 @@
+mod builder;
 mod byte_pair_encoding;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+mod inference_model;
+mod normalizer;
+mod pre_tokenizer;
+mod trained_corpus;
+mod training_config;
 
-pub use byte_pair_encoding::BytePairEncoding;
+pub use builder::{
+    BytePairEncodingBuilder, CharacterSet, MergeExhaustion, MergeStrategy, TokenOverflow,
+    UnknownCharacter, UnknownWord,
+};
+pub use byte_pair_encoding::{
+    analyze_corpus, BytePairEncoding, CacheStats, CorpusStats, Merge, MergeRecord, Mismatch,
+    PaddingSide, TokenizerInfo, TrainingEstimate, TrainingStatus,
+};
+pub use inference_model::InferenceModel;
+pub use normalizer::Normalizer;
+pub use pre_tokenizer::PreTokenizer;
+pub use trained_corpus::TrainedCorpus;
+pub use training_config::TrainingConfig;
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Normalizer {
+    pub lowercase_ascii: bool,
+    pub strip_accents: bool,
+    pub strip_control_characters: bool,
+    pub strip_invisible_marks: bool,
+}
+
+impl Normalizer {
+    const INVISIBLE_MARKS: [char; 16] = [
+        '\u{061C}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{200E}', '\u{200F}', '\u{202A}',
+        '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}', '\u{2060}', '\u{2066}', '\u{2067}',
+        '\u{2068}', '\u{2069}',
+    ];
+
+    pub fn normalize(&self, text: &str) -> String {
+        let mut normalized = text.to_string();
+
+        if self.strip_control_characters {
+            normalized.retain(|c| !c.is_control() || c.is_whitespace());
+        }
+        if self.strip_invisible_marks {
+            normalized.retain(|c| !Self::INVISIBLE_MARKS.contains(&c));
+        }
+        if self.strip_accents {
+            normalized = normalized
+                .nfd()
+                .filter(|c| !is_combining_mark(*c))
+                .nfc()
+                .collect();
+        }
+        if self.lowercase_ascii {
+            normalized.make_ascii_lowercase();
+        }
+
+        normalized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_leaves_text_untouched_by_default() {
+        let normalizer = Normalizer::default();
+
+        assert_eq!("A İ", normalizer.normalize("A İ"));
+    }
+
+    #[test]
+    fn normalize_lowercases_only_ascii_letters() {
+        let normalizer = Normalizer {
+            lowercase_ascii: true,
+            ..Default::default()
+        };
+
+        assert_eq!("a İ", normalizer.normalize("A İ"));
+    }
+
+    #[test]
+    fn normalize_strips_combining_marks() {
+        let normalizer = Normalizer {
+            strip_accents: true,
+            ..Default::default()
+        };
+
+        assert_eq!("cafe naive", normalizer.normalize("café naïve"));
+        assert_eq!("cafe", normalizer.normalize("cafe\u{301}"));
+    }
+
+    #[test]
+    fn normalize_strips_control_characters_except_whitespace() {
+        let normalizer = Normalizer {
+            strip_control_characters: true,
+            ..Default::default()
+        };
+
+        assert_eq!("ab\tc\n", normalizer.normalize("a\u{0}b\tc\u{7f}\n\u{1b}"));
+    }
+
+    #[test]
+    fn normalize_strips_zero_width_and_bidi_marks() {
+        let normalizer = Normalizer {
+            strip_invisible_marks: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "abc",
+            normalizer.normalize("a\u{200D}b\u{2060}\u{202E}c\u{200B}")
+        );
+        assert_eq!("a\u{200D}b", Normalizer::default().normalize("a\u{200D}b"));
+    }
+}
@@ -0,0 +1,106 @@
+use std::iter;
+
+use serde::{Deserialize, Serialize};
+
+use crate::BytePairEncoding;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreTokenizer {
+    pub isolate_punctuation: bool,
+    pub unicode_whitespace: bool,
+    pub group_punctuation: bool,
+    pub split_leading_boundary: bool,
+}
+
+impl PreTokenizer {
+    pub fn pre_tokenize(&self, text: &str) -> Vec<String> {
+        let mut prepped = vec![];
+        let mut word = vec![];
+        let mut isolated = false;
+
+        for (i, c) in text.chars().enumerate() {
+            if !word.is_empty()
+                && !self.continues_run(&word, c)
+                && (isolated || self.is_boundary(c))
+            {
+                prepped.push(word.join(""));
+                word = vec![];
+            }
+
+            isolated = (self.isolate_punctuation
+                && BytePairEncoding::PUNCTUATION.contains(&c)
+                && !c.is_whitespace())
+                || (self.split_leading_boundary && i == 0 && self.is_boundary(c));
+            word.push(c.to_string());
+        }
+        if !word.is_empty() {
+            prepped.push(word.join(""));
+        }
+
+        prepped
+    }
+
+    fn continues_run(&self, word: &[String], c: char) -> bool {
+        self.group_punctuation
+            && BytePairEncoding::PUNCTUATION.contains(&c)
+            && !c.is_whitespace()
+            && word.iter().all(|piece| piece.chars().eq(iter::once(c)))
+    }
+
+    fn is_boundary(&self, c: char) -> bool {
+        BytePairEncoding::PUNCTUATION.contains(&c) || (self.unicode_whitespace && c.is_whitespace())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pre_tokenize_returns_splitted_string() {
+        let expected = vec!["a", " test", "?", " yes", ",", " a", " test", "."];
+        let actual = PreTokenizer::default().pre_tokenize("a test? yes, a test.");
+
+        assert_eq!(expected, actual);
+        assert!(PreTokenizer::default().pre_tokenize("").is_empty());
+    }
+
+    #[test]
+    fn pre_tokenize_splits_on_unicode_whitespace_when_enabled() {
+        let pre_tokenizer = PreTokenizer {
+            unicode_whitespace: true,
+            ..Default::default()
+        };
+
+        assert_eq!(vec!["a", "\u{a0}b"], pre_tokenizer.pre_tokenize("a\u{a0}b"));
+        assert_eq!(
+            vec!["a\u{a0}b"],
+            PreTokenizer::default().pre_tokenize("a\u{a0}b")
+        );
+    }
+
+    #[test]
+    fn pre_tokenize_attaches_or_splits_a_leading_space() {
+        let pre_tokenizer = PreTokenizer {
+            split_leading_boundary: true,
+            ..Default::default()
+        };
+
+        assert_eq!(vec![" test"], PreTokenizer::default().pre_tokenize(" test"));
+        assert_eq!(vec![" ", "test"], pre_tokenizer.pre_tokenize(" test"));
+        assert_eq!(vec!["a", " test"], pre_tokenizer.pre_tokenize("a test"));
+    }
+
+    #[test]
+    fn pre_tokenize_groups_runs_of_identical_punctuation() {
+        let pre_tokenizer = PreTokenizer {
+            group_punctuation: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vec!["a", "...", "!", " ", " b", "?", "!"],
+            pre_tokenizer.pre_tokenize("a...!  b?!")
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use std::io::Error;
+
+use crate::byte_pair_encoding::Map;
+use crate::{BytePairEncoding, MergeRecord, TrainingConfig};
+
+pub struct TrainedCorpus {
+    pub(crate) alphabet: Vec<String>,
+    pub(crate) words: Map<Vec<String>, usize>,
+    pub(crate) only_special_tokens: bool,
+    pub(crate) config: TrainingConfig,
+}
+
+impl TrainedCorpus {
+    pub fn new(corpus: String) -> Self {
+        BytePairEncoding::builder().prepare(corpus)
+    }
+
+    pub fn min_vocab_size(&self) -> usize {
+        self.alphabet.len() + 1
+    }
+
+    pub fn retrain_with_vocab_size(
+        &self,
+        max_vocab_size: usize,
+    ) -> Result<BytePairEncoding, Error> {
+        self.retrain_with_observer(max_vocab_size, |_| {})
+    }
+
+    pub fn retrain_with_observer(
+        &self,
+        max_vocab_size: usize,
+        mut observer: impl FnMut(&MergeRecord),
+    ) -> Result<BytePairEncoding, Error> {
+        BytePairEncoding::train(self, max_vocab_size, &mut observer)
+    }
+}
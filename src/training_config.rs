@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CharacterSet, MergeExhaustion, MergeStrategy, Normalizer, PreTokenizer, TokenOverflow,
+    UnknownCharacter, UnknownWord,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrainingConfig {
+    pub max_vocab_size: usize,
+    pub normalizer: Normalizer,
+    pub pre_tokenizer: PreTokenizer,
+    pub initial_alphabet: Vec<String>,
+    pub character_sets: Vec<CharacterSet>,
+    pub estimated_unique_words: usize,
+    pub isolate_scripts: bool,
+    pub add_prefix_space: bool,
+    pub trim_corpus: bool,
+    pub deduplicate_documents: bool,
+    pub top_k_words: Option<usize>,
+    pub max_alphabet_size: Option<usize>,
+    pub mark_continuations: bool,
+    pub required_words: Vec<String>,
+    pub unknown_word: UnknownWord,
+    pub unknown_character: UnknownCharacter,
+    pub merge_strategy: MergeStrategy,
+    pub reserved_ids: usize,
+    pub merge_exhaustion: MergeExhaustion,
+    pub max_tokens_per_input: Option<usize>,
+    pub token_overflow: TokenOverflow,
+    pub special_tokens: Vec<String>,
+    pub render_leading_space: bool,
+    pub case_insensitive_specials: bool,
+    pub max_edit_distance: Option<usize>,
+    pub cache_capacity: usize,
+    pub post_merge_rules: Vec<(String, String)>,
+    pub skip_special_tokens: bool,
+    pub special_ids_after_vocab: bool,
+}
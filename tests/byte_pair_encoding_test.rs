@@ -1,12 +1,28 @@
-use std::io::ErrorKind;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{Cursor, ErrorKind};
+use std::sync::Arc;
+use std::thread;
 
-use tokenizers_rs::BytePairEncoding;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use tokenizers_rs::{
+    analyze_corpus, BytePairEncoding, CacheStats, CharacterSet, InferenceModel, MergeExhaustion,
+    MergeRecord, MergeStrategy, Mismatch, Normalizer, PaddingSide, PreTokenizer, TokenOverflow,
+    TokenizerInfo, TrainedCorpus, TrainingConfig, TrainingEstimate, TrainingStatus,
+    UnknownCharacter, UnknownWord,
+};
 
 const TEXT: &str = "This is not a token.";
 
+include!("fixtures/embedded.rs");
+
 #[test]
 fn bpe_tokenizes_text() {
-    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 18);
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
 
     let expected = vec![
         "<|startoftext|>".to_string(),
@@ -30,7 +46,7 @@ fn bpe_tokenizes_text() {
 
 #[test]
 fn bpe_throws_error_for_unseen_word() {
-    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 18);
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
 
     let res = tokenizer.tokenize("This token is not real".to_string());
 
@@ -39,7 +55,7 @@ fn bpe_throws_error_for_unseen_word() {
 
 #[test]
 fn bpe_throws_io_error() {
-    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 18);
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
 
     let expected_err_kind = ErrorKind::InvalidInput;
     let expected_err_msg = "Word not found in vocabulary";
@@ -51,3 +67,2758 @@ fn bpe_throws_io_error() {
     assert_eq!(expected_err_kind, actal.kind());
     assert_eq!(expected_err_msg, actal.to_string());
 }
+
+#[test]
+fn bpe_lowercases_only_ascii_when_enabled() {
+    let tokenizer = BytePairEncoding::builder()
+        .lowercase_ascii(true)
+        .train("a İ".to_string(), 10)
+        .unwrap();
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "a".to_string(),
+        " İ".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize("A İ".to_string());
+
+    assert!(tokenizer.normalizer().lowercase_ascii);
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn trained_corpus_retrains_like_fresh_training() {
+    let corpus = TrainedCorpus::new(TEXT.to_string());
+
+    for max_vocab_size in [22, 24] {
+        let expected = BytePairEncoding::from(TEXT.to_string(), max_vocab_size);
+        let actual = corpus.retrain_with_vocab_size(max_vocab_size).unwrap();
+
+        assert_eq!(expected.vocab_size, actual.vocab_size);
+        assert_eq!(expected.tokenizer, actual.tokenizer);
+    }
+}
+
+#[test]
+fn bpe_token_entropy_is_zero_for_a_single_repeated_token() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let actual = tokenizer.token_entropy("...".to_string()).unwrap();
+
+    assert_eq!(0.0, actual);
+}
+
+#[test]
+fn bpe_token_entropy_is_positive_for_mixed_tokens() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let actual = tokenizer
+        .token_entropy("This token is not".to_string())
+        .unwrap();
+
+    assert!(actual > 0.0);
+}
+
+#[test]
+fn bpe_fertility_is_one_when_every_word_is_a_single_token() {
+    let saturated = BytePairEncoding::from(TEXT.to_string(), 100);
+    let partial = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    assert_eq!(1.0, saturated.fertility(TEXT.to_string()).unwrap());
+    assert!(partial.fertility(TEXT.to_string()).unwrap() > 1.0);
+    assert_eq!(0.0, saturated.fertility(String::new()).unwrap());
+}
+
+#[test]
+fn bpe_initial_alphabet_is_part_of_the_vocabulary() {
+    let initial_alphabet = vec!["x".to_string(), "y".to_string(), "z".to_string()];
+
+    let default = BytePairEncoding::from(TEXT.to_string(), 17);
+    let tokenizer = BytePairEncoding::builder()
+        .initial_alphabet(initial_alphabet.clone())
+        .train(TEXT.to_string(), 20)
+        .unwrap();
+
+    for character in initial_alphabet.iter() {
+        assert!(tokenizer.alphabet().contains(character));
+    }
+    assert_eq!(default.alphabet().len() + 3, tokenizer.alphabet().len());
+    assert_eq!(default.vocab_size + 3, tokenizer.vocab_size);
+}
+
+#[test]
+fn bpe_trained_tokenizer_validates() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    assert!(tokenizer.validate().is_ok());
+}
+
+#[test]
+fn bpe_trained_tokenizer_with_required_words_validates() {
+    let tokenizer = BytePairEncoding::builder()
+        .required_words(vec!["token".to_string()])
+        .train(TEXT.to_string(), 18)
+        .unwrap();
+
+    assert!(tokenizer.validate().is_ok());
+}
+
+#[test]
+fn bpe_validate_rejects_corrupted_tokenizer() {
+    let mut tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    tokenizer
+        .tokenizer
+        .insert("xyz".to_string(), vec!["xyz".to_string()]);
+
+    let actual = tokenizer.validate().err().unwrap();
+
+    assert_eq!(ErrorKind::InvalidData, actual.kind());
+}
+
+#[test]
+fn bpe_validate_rejects_out_of_order_merges() {
+    let mut tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    tokenizer.merges.reverse();
+
+    assert!(tokenizer.validate().is_err());
+}
+
+#[test]
+fn bpe_count_tokens_matches_tokenize_length() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    for text in [
+        "This token is not",
+        "...",
+        "This is a token.",
+        "This <|endoftext|> is",
+    ] {
+        let expected = tokenizer.tokenize(text.to_string()).unwrap().len();
+        let actual = tokenizer.count_tokens(text.to_string()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+    assert!(tokenizer.count_tokens("not real".to_string()).is_err());
+
+    let truncating = BytePairEncoding::builder()
+        .max_tokens_per_input(4)
+        .on_token_overflow(TokenOverflow::Truncate)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    let expected = truncating.tokenize(TEXT.to_string()).unwrap().len();
+
+    assert_eq!(4, expected);
+    assert_eq!(expected, truncating.count_tokens(TEXT.to_string()).unwrap());
+}
+
+#[test]
+fn bpe_tokenizes_unseen_standalone_punctuation() {
+    let tokenizer = BytePairEncoding::from("abc abc".to_string(), 14);
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        ".".to_string(),
+        ".".to_string(),
+        ".".to_string(),
+        "!".to_string(),
+        "?".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize("...!?".to_string());
+
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn bpe_estimated_unique_words_does_not_change_training() {
+    let expected = BytePairEncoding::from(TEXT.to_string(), 22);
+    let actual = BytePairEncoding::builder()
+        .estimated_unique_words(1024)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    assert_eq!(expected.vocab_size, actual.vocab_size);
+    assert_eq!(expected.tokenizer, actual.tokenizer);
+    assert_eq!(expected.merges, actual.merges);
+}
+
+#[test]
+fn bpe_is_shareable_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BytePairEncoding>();
+
+    let tokenizer = Arc::new(BytePairEncoding::from(TEXT.to_string(), 22));
+    let expected = tokenizer.tokenize("This token is not".to_string()).unwrap();
+
+    let handles = (0..4)
+        .map(|_| {
+            let tokenizer = Arc::clone(&tokenizer);
+            thread::spawn(move || tokenizer.tokenize("This token is not".to_string()))
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        assert_eq!(expected, handle.join().unwrap().unwrap());
+    }
+}
+
+#[test]
+fn bpe_replaces_unknown_characters_with_bytes() {
+    let tokenizer = BytePairEncoding::builder()
+        .replace_unknown_with_bytes(true)
+        .train(TEXT.to_string(), 278)
+        .unwrap();
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "a".to_string(),
+        "<0xC3>".to_string(),
+        "<0xA9>".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize("aé".to_string());
+
+    assert_eq!(expected, actual.unwrap());
+    assert!(tokenizer.alphabet().contains(&"<0xA9>".to_string()));
+}
+
+#[test]
+fn bpe_special_mask_marks_only_sentinels() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let (tokens, mask) = tokenizer
+        .tokenize_with_special_mask("This token is not".to_string())
+        .unwrap();
+
+    let mut expected = vec![false; tokens.len()];
+    expected[0] = true;
+    expected[tokens.len() - 1] = true;
+
+    assert_eq!(expected, mask);
+}
+
+#[test]
+fn bpe_stops_silently_when_merges_are_exhausted() {
+    let tokenizer = BytePairEncoding::builder()
+        .on_merges_exhausted(MergeExhaustion::StopSilently)
+        .train("abc abc".to_string(), 20)
+        .unwrap();
+
+    assert_eq!(12, tokenizer.vocab_size);
+    assert_eq!(None, tokenizer.training_status());
+}
+
+#[test]
+fn bpe_errors_when_merges_are_exhausted() {
+    let actual = BytePairEncoding::builder()
+        .on_merges_exhausted(MergeExhaustion::Error)
+        .train("abc abc".to_string(), 20);
+
+    assert_eq!(ErrorKind::InvalidInput, actual.err().unwrap().kind());
+}
+
+#[test]
+fn bpe_returns_status_when_merges_are_exhausted() {
+    let exhausted = BytePairEncoding::builder()
+        .on_merges_exhausted(MergeExhaustion::ReturnStatus)
+        .train("abc abc".to_string(), 20)
+        .unwrap();
+    let completed = BytePairEncoding::builder()
+        .on_merges_exhausted(MergeExhaustion::ReturnStatus)
+        .train("abc abc".to_string(), 11)
+        .unwrap();
+
+    assert_eq!(
+        Some(TrainingStatus::MergesExhausted { vocab_size: 12 }),
+        exhausted.training_status()
+    );
+    assert_eq!(Some(TrainingStatus::Completed), completed.training_status());
+}
+
+#[test]
+fn bpe_merge_diff_reports_added_and_removed_merges() {
+    let smaller = BytePairEncoding::from(TEXT.to_string(), 22);
+    let larger = BytePairEncoding::from(TEXT.to_string(), 23);
+
+    let (added, removed) = larger.merge_diff(&smaller);
+
+    assert_eq!(vec![larger.merges.last().unwrap().clone()], added);
+    assert!(removed.is_empty());
+    assert_eq!((vec![], added), smaller.merge_diff(&larger));
+
+    let mut inserted = BytePairEncoding::from(TEXT.to_string(), 22);
+    let merge = ("x".to_string(), "y".to_string());
+    inserted.merges.insert(2, merge.clone());
+
+    assert_eq!((vec![merge.clone()], vec![]), inserted.merge_diff(&smaller));
+    assert_eq!((vec![], vec![merge]), smaller.merge_diff(&inserted));
+}
+
+#[cfg(feature = "fxhash")]
+#[test]
+fn bpe_training_is_independent_of_the_hasher() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let expected = vec![
+        ("i", "s"),
+        ("t", "o"),
+        ("to", "k"),
+        ("tok", "e"),
+        ("toke", "n"),
+        ("o", "t"),
+    ]
+    .into_iter()
+    .map(|(left, right)| (left.to_string(), right.to_string()))
+    .collect::<Vec<(String, String)>>();
+
+    assert_eq!(expected, tokenizer.merges);
+}
+
+#[test]
+fn bpe_tokenize_truncated_stops_at_max_tokens() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "T".to_string(),
+        "h".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let (actual, truncated) = tokenizer
+        .tokenize_truncated("This token is not".to_string(), 4)
+        .unwrap();
+
+    assert!(truncated);
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn bpe_tokenize_truncated_keeps_short_input_intact() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let expected = tokenizer.tokenize("This token".to_string()).unwrap();
+    let (actual, truncated) = tokenizer
+        .tokenize_truncated("This token".to_string(), expected.len())
+        .unwrap();
+
+    assert!(!truncated);
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn bpe_strip_accents_tokenizes_accented_and_plain_forms_alike() {
+    let tokenizer = BytePairEncoding::builder()
+        .strip_accents(true)
+        .train("café au lait".to_string(), 20)
+        .unwrap();
+
+    let accented = tokenizer.tokenize("café".to_string()).unwrap();
+    let plain = tokenizer.tokenize("cafe".to_string()).unwrap();
+
+    assert_eq!(plain, accented);
+    assert!(!tokenizer.alphabet().contains(&"é".to_string()));
+}
+
+#[test]
+fn bpe_info_reflects_builder_configuration() {
+    let tokenizer = BytePairEncoding::builder()
+        .lowercase_ascii(true)
+        .strip_accents(true)
+        .split_leading_boundary(true)
+        .add_prefix_space(true)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let expected = TokenizerInfo {
+        vocab_size: 22,
+        alphabet_size: 15,
+        merge_count: 7,
+        special_tokens: vec!["<|startoftext|>".to_string(), "<|endoftext|>".to_string()],
+        normalizer: Normalizer {
+            lowercase_ascii: true,
+            strip_accents: true,
+            strip_control_characters: false,
+            strip_invisible_marks: false,
+        },
+        pre_tokenizer: PreTokenizer {
+            split_leading_boundary: true,
+            ..Default::default()
+        },
+        add_prefix_space: true,
+        unknown_word: UnknownWord::Error,
+        unknown_character: UnknownCharacter::Error,
+    };
+
+    assert_eq!(expected, tokenizer.info());
+}
+
+#[test]
+fn bpe_tokenize_bytes_replaces_invalid_utf8() {
+    let tokenizer = BytePairEncoding::builder()
+        .replace_unknown_with_bytes(true)
+        .train(TEXT.to_string(), 278)
+        .unwrap();
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "a".to_string(),
+        "<0xEF>".to_string(),
+        "<0xBF>".to_string(),
+        "<0xBD>".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize_bytes(b"a\xFF");
+
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn bpe_tokenize_bytes_errors_on_invalid_utf8_without_fallback() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let actual = tokenizer.tokenize_bytes(b"a\xFF");
+
+    assert_eq!(ErrorKind::InvalidInput, actual.err().unwrap().kind());
+}
+
+#[test]
+fn bpe_tokenize_borrowed_matches_tokenize_without_cloning() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let expected = tokenizer.tokenize("This token is not".to_string()).unwrap();
+    let actual = tokenizer
+        .tokenize_borrowed("This token is not".to_string())
+        .unwrap();
+
+    assert_eq!(expected, actual);
+    assert!(actual.iter().all(|token| matches!(token, Cow::Borrowed(_))));
+
+    let expected = tokenizer
+        .tokenize("This <|endoftext|> is".to_string())
+        .unwrap();
+    let actual = tokenizer
+        .tokenize_borrowed("This <|endoftext|> is".to_string())
+        .unwrap();
+
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn bpe_from_chunks_matches_single_string_training() {
+    let chunks = vec!["This is no".to_string(), "t a token.".to_string()];
+
+    let expected = BytePairEncoding::from(TEXT.to_string(), 22);
+    let actual = BytePairEncoding::from_chunks(chunks.into_iter(), 22).unwrap();
+
+    assert_eq!(expected.alphabet(), actual.alphabet());
+    assert_eq!(expected.merges, actual.merges);
+    assert_eq!(expected.tokenizer, actual.tokenizer);
+}
+
+#[test]
+fn bpe_render_tokens_marks_boundaries_and_whitespace() {
+    let tokens = vec!["This".to_string(), " token".to_string(), "\n".to_string()];
+
+    let expected = "⟨This⟩⟨␣token⟩⟨↵⟩";
+    let actual = BytePairEncoding::render_tokens(&tokens);
+
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn bpe_from_vocab_tokenizes_known_words_without_merges() {
+    let words = vec!["token".to_string(), "text".to_string()];
+    let tokenizer = BytePairEncoding::from_vocab(words, 20);
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "t".to_string(),
+        "o".to_string(),
+        "k".to_string(),
+        "e".to_string(),
+        "n".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize("token".to_string()).unwrap();
+
+    assert_eq!(expected, actual);
+    assert_eq!(tokenizer.tokenizer["token"], actual[1..actual.len() - 1]);
+    assert!(tokenizer.merges.is_empty());
+    assert_eq!(tokenizer.alphabet().len(), tokenizer.vocab_size);
+
+    assert_eq!(
+        vec!["<|startoftext|>", "t", "e", "n", "t", "<|endoftext|>"],
+        tokenizer.tokenize("tent".to_string()).unwrap()
+    );
+    assert_eq!(
+        vec![
+            "<|startoftext|>",
+            "t",
+            "o",
+            "k",
+            "e",
+            "n",
+            " ",
+            "t",
+            "e",
+            "x",
+            "t",
+            "<|endoftext|>"
+        ],
+        tokenizer.tokenize("token text".to_string()).unwrap()
+    );
+}
+
+#[test]
+fn bpe_round_trips_through_its_json_string() {
+    let tokenizer = BytePairEncoding::builder()
+        .lowercase_ascii(true)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let json = tokenizer.to_json().unwrap();
+    let actual = json.parse::<BytePairEncoding>().unwrap();
+
+    assert_eq!(tokenizer.info(), actual.info());
+    assert_eq!(tokenizer.tokenizer, actual.tokenizer);
+    assert_eq!(tokenizer.merges, actual.merges);
+    assert_eq!(tokenizer.alphabet(), actual.alphabet());
+}
+
+#[test]
+fn bpe_round_trips_through_a_saved_file() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let path = env::temp_dir().join("tokenizers_rs_round_trip.json");
+
+    tokenizer.save(&path).unwrap();
+    let actual = BytePairEncoding::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(tokenizer.info(), actual.info());
+    assert_eq!(tokenizer.tokenizer, actual.tokenizer);
+}
+
+#[test]
+fn bpe_rejects_malformed_json() {
+    let actual = "{ not json".parse::<BytePairEncoding>();
+
+    assert_eq!(ErrorKind::InvalidData, actual.err().unwrap().kind());
+}
+
+#[test]
+fn bpe_encode_windows_produces_overlapping_windows() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let tokens = tokenizer.tokenize("This token is not".to_string()).unwrap();
+    let content = &tokens[1..tokens.len() - 1];
+    let windows = tokenizer
+        .encode_windows("This token is not".to_string(), 4, 3)
+        .unwrap();
+
+    assert_eq!(10, content.len());
+    assert_eq!(3, windows.len());
+    for window in windows.iter() {
+        assert_eq!("<|startoftext|>", window[0]);
+        assert_eq!("<|endoftext|>", window[window.len() - 1]);
+    }
+    assert_eq!(windows[0][4], windows[1][1]);
+    assert_eq!(content[3..7], windows[1][1..5]);
+    assert_eq!(content[6..10], windows[2][1..5]);
+}
+
+#[test]
+fn bpe_encode_windows_keeps_a_shorter_final_window() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let windows = tokenizer
+        .encode_windows("This token is not".to_string(), 4, 4)
+        .unwrap();
+
+    assert_eq!(3, windows.len());
+    assert_eq!(4, windows[2].len());
+}
+
+#[test]
+fn bpe_encode_windows_rejects_a_stride_beyond_the_window() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let error = tokenizer
+        .encode_windows("This token is not".to_string(), 3, 4)
+        .unwrap_err();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+}
+
+#[test]
+fn bpe_unknown_word_policy_replays_merges_for_unseen_words() {
+    let tokenizer = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "token".to_string(),
+        "is".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize("tokenis".to_string());
+
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn bpe_unknown_character_policy_errors() {
+    let tokenizer = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .unknown_character(UnknownCharacter::Error)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let actual = tokenizer.tokenize("toké".to_string()).err().unwrap();
+
+    assert_eq!(ErrorKind::InvalidInput, actual.kind());
+    assert_eq!("Character \"é\" not found in alphabet", actual.to_string());
+}
+
+#[test]
+fn bpe_unknown_character_policy_falls_back_to_bytes() {
+    let tokenizer = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .unknown_character(UnknownCharacter::ByteFallback)
+        .train(TEXT.to_string(), 278)
+        .unwrap();
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "tok".to_string(),
+        "<0xC3>".to_string(),
+        "<0xA9>".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize("toké".to_string());
+
+    assert_eq!(expected, actual.unwrap());
+}
+
+#[test]
+fn bpe_unknown_character_policy_emits_unk() {
+    let tokenizer = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .unknown_character(UnknownCharacter::Unk)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let expected = vec![
+        "<|startoftext|>".to_string(),
+        "tok".to_string(),
+        "<|unk|>".to_string(),
+        "<|endoftext|>".to_string(),
+    ];
+    let actual = tokenizer.tokenize("toké".to_string());
+
+    assert_eq!(expected, actual.unwrap());
+    assert!(tokenizer.is_special("<|unk|>"));
+    assert!(tokenizer.special_tokens().contains(&"<|unk|>".to_string()));
+    assert_eq!(
+        vec![true, false, true, true],
+        tokenizer
+            .tokenize_with_special_mask("toké".to_string())
+            .unwrap()
+            .1
+    );
+    assert!(tokenizer.self_check_specials().is_ok());
+}
+
+#[test]
+fn bpe_unknown_word_policy_errors_before_checking_characters() {
+    let tokenizer = BytePairEncoding::builder()
+        .unknown_character(UnknownCharacter::Unk)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let actual = tokenizer.tokenize("toké".to_string()).err().unwrap();
+
+    assert_eq!("Word not found in vocabulary", actual.to_string());
+}
+
+#[test]
+fn trained_corpus_from_word_counts_matches_corpus_training() {
+    let word_counts = vec![
+        ("This", 1),
+        (" is", 1),
+        (" not", 1),
+        (" a", 1),
+        (" token", 1),
+        (".", 1),
+    ]
+    .into_iter()
+    .map(|(word, freq)| (word.to_string(), freq))
+    .collect::<HashMap<String, usize>>();
+
+    let expected = BytePairEncoding::from(TEXT.to_string(), 22);
+    let actual = BytePairEncoding::builder()
+        .prepare_word_counts(word_counts)
+        .retrain_with_vocab_size(22)
+        .unwrap();
+
+    assert_eq!(expected.alphabet(), actual.alphabet());
+    assert_eq!(expected.merges, actual.merges);
+    assert_eq!(expected.tokenizer, actual.tokenizer);
+}
+
+#[test]
+fn bpe_encode_maps_tokens_to_ids() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let tokens = tokenizer.tokenize("This token".to_string()).unwrap();
+    let ids = tokenizer.encode("This token".to_string()).unwrap();
+
+    assert_eq!(tokens.len(), ids.len());
+    assert_eq!(0, ids[0]);
+    assert_eq!(1, ids[ids.len() - 1]);
+    for (token, id) in tokens.iter().zip(ids.iter()) {
+        assert_eq!(Some(token.clone()), tokenizer.id_to_token(*id));
+    }
+}
+
+#[test]
+fn bpe_reserved_ids_offset_learned_tokens() {
+    let tokenizer = BytePairEncoding::builder()
+        .reserved_ids(100)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    assert_eq!(0..100, tokenizer.reserved_ids());
+    assert_eq!(Some(100), tokenizer.token_to_id("<|startoftext|>"));
+    assert_eq!(None, tokenizer.id_to_token(0));
+    assert_eq!(None, tokenizer.id_to_token(99));
+    assert_eq!(
+        Some("<|startoftext|>".to_string()),
+        tokenizer.id_to_token(100)
+    );
+    assert!(tokenizer
+        .encode("This token".to_string())
+        .unwrap()
+        .iter()
+        .all(|id| *id >= 100));
+}
+
+#[test]
+fn bpe_add_special_token_keeps_existing_ids() {
+    let mut tokenizer = BytePairEncoding::builder()
+        .reserved_ids(10)
+        .add_special_token("<|sep|>")
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    let vocab = tokenizer.vocab();
+    let ids = tokenizer.encode(TEXT.to_string()).unwrap();
+
+    tokenizer.add_special_token("<|pad|>").unwrap();
+    assert_eq!(vocab[..], tokenizer.vocab()[..vocab.len()]);
+    assert_eq!(ids, tokenizer.encode(TEXT.to_string()).unwrap());
+    assert_eq!(Some(vocab.len() + 10), tokenizer.token_to_id("<|pad|>"));
+
+    let id = tokenizer.token_to_id("is");
+    tokenizer.add_special_token("is").unwrap();
+    assert_eq!(id, tokenizer.token_to_id("is"));
+    assert_eq!(vocab.len() + 1, tokenizer.vocab().len());
+
+    let loaded = tokenizer
+        .to_json()
+        .unwrap()
+        .parse::<BytePairEncoding>()
+        .unwrap();
+    assert_eq!(tokenizer.vocab(), loaded.vocab());
+}
+
+#[test]
+fn bpe_detokenize_round_trips_repeated_whitespace() {
+    let text = "This  is   not\n\na token.";
+    let tokenizer = BytePairEncoding::from(text.to_string(), 22);
+
+    let tokens = tokenizer.tokenize(text.to_string()).unwrap();
+    let actual = tokenizer.detokenize(&tokens).unwrap();
+
+    assert_eq!(text, actual);
+}
+
+#[test]
+fn bpe_detokenize_decodes_byte_tokens() {
+    let tokenizer = BytePairEncoding::builder()
+        .replace_unknown_with_bytes(true)
+        .train(TEXT.to_string(), 278)
+        .unwrap();
+
+    let tokens = tokenizer.tokenize("This café".to_string()).unwrap();
+    let actual = tokenizer.detokenize(&tokens).unwrap();
+
+    assert_eq!("This café", actual);
+}
+
+#[test]
+fn analyze_corpus_reports_corpus_statistics() {
+    let stats = analyze_corpus(TEXT);
+
+    let expected_top_bigrams = vec![
+        ((" ".to_string(), "a".to_string()), 1),
+        ((" ".to_string(), "i".to_string()), 1),
+    ];
+
+    assert_eq!(12, stats.unique_characters);
+    assert_eq!(6, stats.unique_words);
+    assert_eq!(6, stats.total_words);
+    assert_eq!(10, stats.top_bigrams.len());
+    assert_eq!(("i".to_string(), "s".to_string()), stats.top_bigrams[0].0);
+    assert_eq!(2, stats.top_bigrams[0].1);
+    assert_eq!(expected_top_bigrams, stats.top_bigrams[1..3]);
+
+    let empty = analyze_corpus("");
+    assert_eq!(0, empty.total_words);
+    assert_eq!(0, empty.unique_words);
+}
+
+#[test]
+fn bpe_strict_detokenize_requires_sentinels() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let tokens = tokenizer.tokenize("This token".to_string()).unwrap();
+    let unwrapped = tokens[1..tokens.len() - 1].to_vec();
+
+    assert_eq!(
+        "This token",
+        tokenizer.detokenize_with(&tokens, true).unwrap()
+    );
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        tokenizer
+            .detokenize_with(&unwrapped, true)
+            .err()
+            .unwrap()
+            .kind()
+    );
+    assert!(tokenizer.detokenize(&tokens[1..]).is_err());
+}
+
+#[test]
+fn bpe_lenient_detokenize_ignores_missing_sentinels() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let tokens = tokenizer.tokenize("This token".to_string()).unwrap();
+
+    for slice in [
+        &tokens[..],
+        &tokens[1..],
+        &tokens[..tokens.len() - 1],
+        &tokens[1..tokens.len() - 1],
+    ] {
+        assert_eq!(
+            "This token",
+            tokenizer.detokenize_with(slice, false).unwrap()
+        );
+    }
+}
+
+#[test]
+fn bpe_encode_batch_matches_encode_in_order() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let texts: Vec<String> = ["This token", "is not", "a", "This is $", "token."]
+        .iter()
+        .map(|text| text.to_string())
+        .collect();
+
+    let batch = tokenizer.encode_batch(texts.clone());
+
+    assert_eq!(texts.len(), batch.len());
+    for (text, encoded) in texts.into_iter().zip(batch) {
+        match tokenizer.encode(text) {
+            Ok(expected) => assert_eq!(expected, encoded.unwrap()),
+            Err(expected) => assert_eq!(expected.kind(), encoded.err().unwrap().kind()),
+        }
+    }
+}
+
+#[test]
+fn bpe_sample_tokenize_is_reproducible_with_seed() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let text = "This token is not a token.";
+
+    let sample = |seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..10)
+            .map(|_| {
+                tokenizer
+                    .sample_tokenize(text.to_string(), 0.1, &mut rng)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let samples = sample(7);
+    assert_eq!(samples, sample(7));
+    for tokens in samples.iter() {
+        assert_eq!(text, tokenizer.detokenize(tokens).unwrap(),);
+    }
+    assert!(samples.iter().any(|tokens| tokens != &samples[0]));
+}
+
+#[test]
+fn bpe_sample_tokenize_with_large_alpha_is_greedy() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let text = "This token is not a token.";
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let greedy = tokenizer.tokenize(text.to_string()).unwrap();
+    for _ in 0..10 {
+        let sampled = tokenizer
+            .sample_tokenize(text.to_string(), 50.0, &mut rng)
+            .unwrap();
+        assert_eq!(greedy, sampled);
+    }
+}
+
+#[test]
+fn bpe_sample_tokenize_weights_tied_splits_by_token_scores() {
+    let tokenizer = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .train("bc bc bc abab".to_string(), 12)
+        .unwrap();
+    assert!(tokenizer.token_score("bc") > tokenizer.token_score("ab"));
+
+    let expected = tokenizer.tokenize("abc".to_string()).unwrap();
+    assert_eq!(
+        vec!["<|startoftext|>", "a", "bc", "<|endoftext|>"],
+        expected
+    );
+    for seed in 0..50 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sampled = tokenizer
+            .sample_tokenize("abc".to_string(), 1000.0, &mut rng)
+            .unwrap();
+        assert_eq!(expected, sampled);
+    }
+}
+
+#[test]
+fn bpe_tokenize_empty_returns_sentinels() {
+    let mut tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let expected = vec!["<|startoftext|>".to_string(), "<|endoftext|>".to_string()];
+    assert_eq!(expected, tokenizer.tokenize("".to_string()).unwrap());
+    assert_eq!(expected, tokenizer.tokenize(" \n ".to_string()).unwrap());
+    assert_eq!(
+        expected,
+        tokenizer.tokenize_borrowed("".to_string()).unwrap()
+    );
+
+    tokenizer.add_special_token("\n\n").unwrap();
+    assert_eq!(
+        vec!["<|startoftext|>", "\n\n", "<|endoftext|>"],
+        tokenizer.tokenize("\n\n".to_string()).unwrap()
+    );
+}
+
+fn gpt2_escape(token: &str) -> String {
+    let mut shifted = 0;
+    let chars = (0..=u8::MAX)
+        .map(|byte| match byte {
+            b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF => char::from(byte),
+            _ => {
+                shifted += 1;
+                char::from_u32(255 + shifted).unwrap()
+            }
+        })
+        .collect::<Vec<char>>();
+
+    token.bytes().map(|byte| chars[usize::from(byte)]).collect()
+}
+
+fn gpt2_round_trip(tokenizer: &BytePairEncoding, name: &str) -> BytePairEncoding {
+    let vocab = tokenizer
+        .vocab()
+        .into_iter()
+        .enumerate()
+        .map(|(id, token)| (gpt2_escape(&token), id))
+        .collect::<HashMap<String, usize>>();
+
+    let dir = env::temp_dir();
+    let vocab_path = dir.join(format!("tokenizers_rs_{name}_vocab.json"));
+    let merges_path = dir.join(format!("tokenizers_rs_{name}_merges.txt"));
+    fs::write(&vocab_path, serde_json::to_string(&vocab).unwrap()).unwrap();
+    fs::write(&merges_path, tokenizer.merges_to_string()).unwrap();
+
+    let loaded = BytePairEncoding::from_gpt2_files(&vocab_path, &merges_path).unwrap();
+    fs::remove_file(vocab_path).unwrap();
+    fs::remove_file(merges_path).unwrap();
+
+    loaded
+}
+
+#[test]
+fn bpe_merges_to_string_round_trips_through_gpt2_files() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 30);
+    assert!(tokenizer.merges.iter().any(|(left, _)| left.contains(' ')));
+
+    let merges = tokenizer.merges_to_string();
+    assert!(merges.starts_with("#version: 0.2\n"));
+    assert_eq!(tokenizer.merges.len() + 1, merges.lines().count());
+    assert!(merges
+        .lines()
+        .skip(1)
+        .all(|line| line.split(' ').count() == 2));
+
+    let loaded = gpt2_round_trip(&tokenizer, "gpt2");
+    assert_eq!(tokenizer.merges, loaded.merges);
+    assert_eq!(tokenizer.alphabet(), loaded.alphabet());
+    assert_eq!(tokenizer.vocab(), loaded.vocab());
+    assert_eq!(
+        tokenizer.encode(TEXT.to_string()).unwrap(),
+        loaded.encode(TEXT.to_string()).unwrap()
+    );
+    assert_eq!(
+        tokenizer.tokenize(TEXT.to_string()).unwrap(),
+        loaded.tokenize(TEXT.to_string()).unwrap()
+    );
+}
+
+#[test]
+fn bpe_from_gpt2_files_keeps_the_vocab_json_ids() {
+    let dir = env::temp_dir();
+    let vocab_path = dir.join("tokenizers_rs_gpt2_ids_vocab.json");
+    let merges_path = dir.join("tokenizers_rs_gpt2_ids_merges.txt");
+    fs::write(&merges_path, "#version: 0.2\na b\n").unwrap();
+
+    fs::write(&vocab_path, r#"{"a":2,"b":1,"ab":0}"#).unwrap();
+    let loaded = BytePairEncoding::from_gpt2_files(&vocab_path, &merges_path).unwrap();
+    assert_eq!(Some(2), loaded.token_to_id("a"));
+    assert_eq!(Some(1), loaded.token_to_id("b"));
+    assert_eq!(Some(0), loaded.token_to_id("ab"));
+    assert_eq!(0, loaded.encode("ab".to_string()).unwrap()[1]);
+
+    fs::write(&vocab_path, r#"{"a":5,"b":3,"ab":0}"#).unwrap();
+    let error = BytePairEncoding::from_gpt2_files(&vocab_path, &merges_path)
+        .err()
+        .unwrap();
+    fs::remove_file(vocab_path).unwrap();
+    fs::remove_file(merges_path).unwrap();
+
+    assert_eq!(ErrorKind::InvalidData, error.kind());
+}
+
+#[test]
+fn bpe_merges_to_string_escapes_tokens_that_look_escaped() {
+    let tokenizer = BytePairEncoding::from("aĠ aĠ aĠ b é".to_string(), 16);
+    assert!(tokenizer
+        .merges
+        .contains(&("a".to_string(), "Ġ".to_string())));
+
+    let loaded = gpt2_round_trip(&tokenizer, "gpt2_escaped");
+    assert_eq!(tokenizer.merges, loaded.merges);
+    assert_eq!(tokenizer.alphabet(), loaded.alphabet());
+}
+
+#[test]
+fn bpe_train_with_observer_records_each_merge() {
+    let mut records = vec![];
+    let tokenizer = BytePairEncoding::builder()
+        .train_with_observer("a test? yes, a test.".to_string(), 20, |record| {
+            records.push(record.clone())
+        })
+        .unwrap();
+
+    assert_eq!(tokenizer.merges.len(), records.len());
+    assert_eq!(
+        MergeRecord {
+            pair: ("e".to_string(), "s".to_string()),
+            frequency: 3,
+            token: "es".to_string(),
+        },
+        records[0]
+    );
+    for (record, merge) in records.iter().zip(tokenizer.merges.iter()) {
+        assert_eq!(merge, &record.pair);
+    }
+}
+
+#[test]
+fn bpe_rebuild_word_map_follows_current_merges() {
+    let mut tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    assert_eq!(
+        vec![" ", "token", "."],
+        tokenizer.tokenize(" token.".to_string()).unwrap()[1..4]
+    );
+
+    tokenizer.merges.retain(|(left, _)| left != "toke");
+    tokenizer.rebuild_word_map(TEXT);
+
+    assert_eq!(
+        vec![" ", "toke", "n"],
+        tokenizer.tokenize(" token".to_string()).unwrap()[1..4]
+    );
+    assert_eq!(
+        vec!["T", "h", "is"],
+        tokenizer.tokenize("This".to_string()).unwrap()[1..4]
+    );
+}
+
+#[test]
+fn bpe_isolate_scripts_forbids_cross_script_merges() {
+    let corpus = "daд daд daд mир mир daд".to_string();
+    let script = |c: char| c.is_ascii_alphabetic();
+
+    let mixed = BytePairEncoding::builder()
+        .train(corpus.clone(), 30)
+        .unwrap();
+    assert!(mixed.merges.iter().any(|(left, right)| {
+        let (left, right) = (left.chars().last().unwrap(), right.chars().next().unwrap());
+        left != ' ' && script(left) != script(right)
+    }));
+
+    let isolated = BytePairEncoding::builder()
+        .isolate_scripts(true)
+        .train(corpus, 30)
+        .unwrap();
+    assert!(!isolated.merges.is_empty());
+    for (left, right) in isolated.merges.iter() {
+        let letters = left
+            .chars()
+            .chain(right.chars())
+            .filter(|c| c.is_alphabetic())
+            .collect::<Vec<char>>();
+        assert!(letters.iter().all(|c| script(*c) == script(letters[0])));
+    }
+}
+
+#[cfg(feature = "fuzzing")]
+#[test]
+fn fuzz_targets_survive_minimized_inputs() {
+    use tokenizers_rs::fuzz::{fuzz_tokenize, fuzz_train};
+
+    for data in [
+        &b""[..],
+        &[0],
+        &[7],
+        &[1, b' '],
+        &[2, b'\n', 0xff],
+        &[255, b'a', b'b'],
+    ] {
+        fuzz_train(data);
+        fuzz_tokenize(data);
+    }
+}
+
+#[test]
+fn bpe_min_vocab_for_coverage_searches_under_the_builder_policy() {
+    assert_eq!(
+        Some(17),
+        BytePairEncoding::min_vocab_for_coverage(TEXT, "This is a token")
+    );
+    assert_eq!(
+        None,
+        BytePairEncoding::min_vocab_for_coverage(TEXT, "not this token")
+    );
+
+    let replay = BytePairEncoding::builder().unknown_word(UnknownWord::Replay);
+    let size = replay
+        .min_vocab_for_coverage(TEXT, "not this token")
+        .unwrap();
+    assert_eq!(17, size);
+    assert!(replay
+        .train(TEXT.to_string(), size)
+        .unwrap()
+        .tokenize("not this token".to_string())
+        .is_ok());
+    assert_eq!(None, replay.min_vocab_for_coverage(TEXT, "zebra"));
+
+    let capped = BytePairEncoding::builder()
+        .max_tokens_per_input(8)
+        .on_token_overflow(TokenOverflow::Error);
+    let size = capped
+        .min_vocab_for_coverage(TEXT, "This is a token")
+        .unwrap();
+    assert!(size > 17);
+    assert!(capped
+        .train(TEXT.to_string(), size - 1)
+        .unwrap()
+        .tokenize("This is a token".to_string())
+        .is_err());
+}
+
+#[test]
+fn bpe_encode_u32_matches_usize_ids() {
+    let tokenizer = BytePairEncoding::builder()
+        .reserved_ids(100)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let ids = tokenizer.encode(TEXT.to_string()).unwrap();
+    let compact = tokenizer.encode_u32(TEXT.to_string()).unwrap();
+
+    assert_eq!(
+        ids.into_iter().map(|id| id as u32).collect::<Vec<u32>>(),
+        compact
+    );
+    assert!(tokenizer.encode_u32("zebra".to_string()).is_err());
+}
+
+#[test]
+fn bpe_add_prefix_space_treats_first_word_like_interior_words() {
+    let tokenizer = BytePairEncoding::builder()
+        .add_prefix_space(true)
+        .train("token a token".to_string(), 20)
+        .unwrap();
+
+    let first = tokenizer.tokenize("token".to_string()).unwrap();
+    let interior = tokenizer.tokenize(" token".to_string()).unwrap();
+
+    assert_eq!(" token", first[1]);
+    assert_eq!(first, interior);
+    assert_eq!("token", tokenizer.detokenize(&first).unwrap());
+    assert_eq!("token", tokenizer.detokenize(&interior).unwrap());
+
+    let without = BytePairEncoding::builder()
+        .train("token a token".to_string(), 20)
+        .unwrap();
+    assert_ne!(
+        without.tokenize("token".to_string()).unwrap()[1],
+        without.tokenize(" token".to_string()).unwrap()[1]
+    );
+}
+
+#[test]
+fn bpe_words_containing_lists_words_using_a_token() {
+    let tokenizer = BytePairEncoding::from_n_merges("a test? yes, a test.".to_string(), 1);
+
+    assert_eq!(
+        vec![" test".to_string(), " yes".to_string()],
+        tokenizer.words_containing("es")
+    );
+    assert!(tokenizer.words_containing("zz").is_empty());
+}
+
+#[test]
+fn bpe_from_reader_matches_string_constructor() {
+    let expected = BytePairEncoding::from(TEXT.to_string(), 22);
+    let actual = BytePairEncoding::from_reader(Cursor::new(TEXT), 22).unwrap();
+
+    assert_eq!(expected.merges, actual.merges);
+    assert_eq!(expected.vocab(), actual.vocab());
+
+    let invalid = Cursor::new(vec![b'a', 0xff, b'b']);
+    assert_eq!(
+        ErrorKind::InvalidData,
+        BytePairEncoding::from_reader(invalid, 22)
+            .err()
+            .unwrap()
+            .kind()
+    );
+}
+
+#[test]
+fn bpe_trim_corpus_drops_surrounding_blank_lines() {
+    let padded = format!("\n  \n{TEXT}\n\n \t\n");
+
+    let trimmed = BytePairEncoding::builder()
+        .trim_corpus(true)
+        .train(padded.clone(), 22)
+        .unwrap();
+    let expected = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    assert_eq!(expected.merges, trimmed.merges);
+    assert_eq!(expected.vocab(), trimmed.vocab());
+    assert_eq!(expected.tokenizer, trimmed.tokenizer);
+
+    let untrimmed = BytePairEncoding::builder().train(padded, 22).unwrap();
+    assert_ne!(expected.vocab(), untrimmed.vocab());
+}
+
+#[test]
+fn bpe_token_score_favors_frequent_merges() {
+    let tokenizer = BytePairEncoding::from("a test? yes, a test.".to_string(), 20);
+
+    let es = tokenizer.token_score("es").unwrap();
+    let test = tokenizer.token_score(" test").unwrap();
+
+    assert!(es > test);
+    assert!(es <= 0.0);
+    assert_eq!(None, tokenizer.token_score("e"));
+    assert_eq!(None, tokenizer.token_score("zebra"));
+}
+
+#[test]
+fn bpe_cache_stats_track_replayed_words() {
+    let tokenizer = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .cache_capacity(1)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    assert_eq!(CacheStats::default(), tokenizer.cache_stats());
+
+    let first = tokenizer.tokenize("tot".to_string()).unwrap();
+    assert_eq!(
+        CacheStats {
+            hits: 0,
+            misses: 1,
+            entries: 1
+        },
+        tokenizer.cache_stats()
+    );
+
+    let second = tokenizer.tokenize("tot".to_string()).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(
+        CacheStats {
+            hits: 1,
+            misses: 1,
+            entries: 1
+        },
+        tokenizer.cache_stats()
+    );
+
+    tokenizer.tokenize("This".to_string()).unwrap();
+    assert_eq!(1, tokenizer.cache_stats().hits);
+
+    tokenizer.tokenize("oat".to_string()).unwrap();
+    assert_eq!(1, tokenizer.cache_stats().entries);
+
+    tokenizer.clear_cache();
+    assert_eq!(CacheStats::default(), tokenizer.cache_stats());
+
+    let uncached = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    uncached.tokenize("tot".to_string()).unwrap();
+    assert_eq!(CacheStats::default(), uncached.cache_stats());
+}
+
+#[test]
+fn bpe_isolate_punctuation_splits_word_leading_punctuation() {
+    let corpus = ",a yes, a".to_string();
+
+    let folded = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .train(corpus.clone(), 12)
+        .unwrap();
+    assert!(folded.tokenizer.contains_key(",a"));
+    assert_eq!(
+        vec![",", "a"],
+        folded.tokenize(",a".to_string()).unwrap()[1..3]
+    );
+
+    let isolated = BytePairEncoding::builder()
+        .isolate_punctuation(true)
+        .train(corpus, 12)
+        .unwrap();
+    assert!(!isolated.tokenizer.contains_key(",a"));
+    assert!(isolated.tokenizer.contains_key(" yes"));
+    assert_eq!(
+        vec![",", "a"],
+        isolated.tokenize(",a".to_string()).unwrap()[1..3]
+    );
+    assert!(isolated
+        .merges
+        .iter()
+        .all(|(left, right)| left != "," && right != ","));
+}
+
+#[test]
+fn bpe_union_places_shared_merges_at_earliest_position() {
+    let first = BytePairEncoding::from_n_merges("a test? yes, a test.".to_string(), 3);
+    let second = BytePairEncoding::from_n_merges("ok ok ok ok eskimo esk es".to_string(), 3);
+    let shared = ("e".to_string(), "s".to_string());
+    assert_eq!(Some(0), first.merges.iter().position(|m| m == &shared));
+    assert_eq!(Some(1), second.merges.iter().position(|m| m == &shared));
+
+    let expected = vec![
+        ("e", "s"),
+        ("o", "k"),
+        ("t", "es"),
+        ("tes", "t"),
+        (" ", "ok"),
+    ]
+    .into_iter()
+    .map(|(left, right)| (left.to_string(), right.to_string()))
+    .collect::<Vec<(String, String)>>();
+
+    let union = first.union(&second);
+    assert_eq!(expected, union.merges);
+    assert_eq!(union.alphabet().len() + 5, union.vocab_size);
+    assert!(union.validate().is_ok());
+
+    let reversed = second.union(&first);
+    assert_eq!(shared, reversed.merges[1]);
+    assert_eq!(("o".to_string(), "k".to_string()), reversed.merges[0]);
+
+    assert_eq!(
+        vec![" ", "test"],
+        union.tokenize(" test".to_string()).unwrap()[1..3]
+    );
+    assert!(union.tokenize(" ok".to_string()).is_ok());
+}
+
+#[test]
+fn bpe_save_vocab_csv_escapes_tokens() {
+    let tokenizer = BytePairEncoding::from("a test? yes, a test.".to_string(), 20);
+    let path = env::temp_dir().join("tokenizers_rs_vocab.csv");
+    tokenizer.save_vocab_csv(&path).unwrap();
+
+    let csv = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    assert_eq!(vec!["token", "id", "creation_frequency"], rows[0]);
+    assert_eq!(tokenizer.vocab().len() + 1, rows.len());
+
+    let comma = rows.iter().find(|row| row[0] == ",").unwrap();
+    assert_eq!(tokenizer.token_to_id(",").unwrap().to_string(), comma[1]);
+    assert!(csv.contains("\",\","));
+    assert!(rows.iter().any(|row| row[0] == "\n"));
+
+    let es = rows.iter().find(|row| row[0] == "es").unwrap();
+    assert_eq!("3", es[2]);
+}
+
+#[test]
+fn bpe_top_k_words_ignores_rare_words() {
+    let corpus = "ab ab ab ab cd cd".to_string();
+    let uses_rare = |tokenizer: &BytePairEncoding| {
+        tokenizer
+            .merges
+            .iter()
+            .any(|(left, right)| format!("{left}{right}").contains(['c', 'd']))
+    };
+
+    let all = BytePairEncoding::builder()
+        .train(corpus.clone(), 20)
+        .unwrap();
+    assert!(uses_rare(&all));
+
+    let top = BytePairEncoding::builder()
+        .top_k_words(1)
+        .train(corpus, 20)
+        .unwrap();
+    assert!(!top.merges.is_empty());
+    assert!(!uses_rare(&top));
+    assert!(top.tokenizer.contains_key(" ab"));
+    assert!(!top.tokenizer.contains_key(" cd"));
+}
+
+#[test]
+fn bpe_tokenize_with_budget_caps_decoded_bytes() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let (tokens, truncated) = tokenizer
+        .tokenize_with_budget(TEXT.to_string(), 12)
+        .unwrap();
+    let decoded = tokenizer.detokenize(&tokens).unwrap();
+
+    assert!(truncated);
+    assert!(decoded.len() <= 12);
+    assert_eq!("This is not ", decoded);
+    assert_eq!(Some(&"<|endoftext|>".to_string()), tokens.last());
+
+    let (tokens, truncated) = tokenizer
+        .tokenize_with_budget(TEXT.to_string(), TEXT.len())
+        .unwrap();
+    assert!(!truncated);
+    assert_eq!(tokenizer.tokenize(TEXT.to_string()).unwrap(), tokens);
+}
+
+#[test]
+fn bpe_merge_path_follows_the_first_learned_merge() {
+    let first = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .train("es est est est".to_string(), 11)
+        .unwrap();
+    let second = BytePairEncoding::from_n_merges("st st st est est".to_string(), 2);
+    let union = first.union(&second);
+
+    let paths =
+        [("es", "t"), ("e", "st")].map(|(left, right)| (left.to_string(), right.to_string()));
+    assert!(paths.iter().all(|path| union.merges.contains(path)));
+
+    assert_eq!(Some(&paths[0]), union.canonical_merge("est"));
+    assert_eq!(
+        vec![("e".to_string(), "s".to_string()), paths[0].clone()],
+        union.merge_path("est")
+    );
+    assert_eq!(None, union.canonical_merge("e"));
+    assert!(union.merge_path("e").is_empty());
+
+    let learned = union.tokenize(" est".to_string()).unwrap();
+    let replayed = union.tokenize("est ests".to_string()).unwrap();
+    assert_eq!(vec![" ", "est"], learned[1..3]);
+    assert_eq!(vec!["est", " ", "est", "s"], replayed[1..5]);
+    assert_eq!(
+        1,
+        union.vocab().iter().filter(|token| *token == "est").count()
+    );
+}
+
+#[test]
+fn bpe_vocab_size_below_floor_reports_minimum() {
+    let corpus = TrainedCorpus::new(TEXT.to_string());
+    assert_eq!(17, corpus.min_vocab_size());
+    assert!(corpus.retrain_with_vocab_size(17).is_ok());
+
+    let error = BytePairEncoding::builder()
+        .train(TEXT.to_string(), 16)
+        .err()
+        .unwrap();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+    assert!(error.to_string().contains("minimum of 17"));
+    assert!(error.to_string().contains("16 alphabet characters"));
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn bpe_binary_round_trip_is_smaller_than_json() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let dir = env::temp_dir();
+    let bin_path = dir.join("tokenizers_rs_round_trip.bin");
+    let json_path = dir.join("tokenizers_rs_round_trip_bin.json");
+
+    tokenizer.save_bin(&bin_path).unwrap();
+    tokenizer.save(&json_path).unwrap();
+    let loaded = BytePairEncoding::load_bin(&bin_path).unwrap();
+
+    let bin_size = fs::metadata(&bin_path).unwrap().len();
+    let json_size = fs::metadata(&json_path).unwrap().len();
+    fs::remove_file(&json_path).unwrap();
+
+    assert!(bin_size < json_size);
+    assert_eq!(tokenizer.merges, loaded.merges);
+    assert_eq!(tokenizer.tokenizer, loaded.tokenizer);
+    assert_eq!(tokenizer.vocab(), loaded.vocab());
+    assert_eq!(
+        tokenizer.tokenize(TEXT.to_string()).unwrap(),
+        loaded.tokenize(TEXT.to_string()).unwrap()
+    );
+
+    fs::write(&bin_path, b"{}").unwrap();
+    let error = BytePairEncoding::load_bin(&bin_path).err().unwrap();
+    fs::remove_file(&bin_path).unwrap();
+    assert_eq!(ErrorKind::InvalidData, error.kind());
+}
+
+#[test]
+fn bpe_strip_control_characters_keeps_nul_out_of_alphabet() {
+    let corpus = "This is\u{0} not a token.\u{7f}".to_string();
+
+    let polluted = BytePairEncoding::builder()
+        .train(corpus.clone(), 22)
+        .unwrap();
+    assert!(polluted.alphabet().contains(&"\u{0}".to_string()));
+
+    let tokenizer = BytePairEncoding::builder()
+        .strip_control_characters(true)
+        .train(corpus, 22)
+        .unwrap();
+    assert!(!tokenizer.alphabet().contains(&"\u{0}".to_string()));
+    assert!(!tokenizer.alphabet().contains(&"\u{7f}".to_string()));
+    assert_eq!(
+        tokenizer.tokenize(" not a token.".to_string()).unwrap(),
+        tokenizer
+            .tokenize(" not a\u{0} token.".to_string())
+            .unwrap()
+    );
+}
+
+#[test]
+fn bpe_tokenize_grouped_pairs_words_with_tokens() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let expected = vec![
+        ("<|startoftext|>", vec!["<|startoftext|>"]),
+        ("This", vec!["T", "h", "is"]),
+        (" is", vec![" ", "is"]),
+        (" not", vec![" ", "n", "ot"]),
+        (".", vec!["."]),
+        ("<|endoftext|>", vec!["<|endoftext|>"]),
+    ]
+    .into_iter()
+    .map(|(word, tokens)| {
+        (
+            word.to_string(),
+            tokens
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<String>>(),
+        )
+    })
+    .collect::<Vec<(String, Vec<String>)>>();
+
+    let grouped = tokenizer
+        .tokenize_grouped("This is not.".to_string())
+        .unwrap();
+    assert_eq!(expected, grouped);
+    assert_eq!(
+        tokenizer.tokenize("This is not.".to_string()).unwrap(),
+        grouped
+            .into_iter()
+            .flat_map(|(_, tokens)| tokens)
+            .collect::<Vec<String>>()
+    );
+}
+
+#[test]
+fn bpe_max_tokens_per_input_errors_or_truncates() {
+    let long = TEXT.repeat(10);
+
+    let strict = BytePairEncoding::builder()
+        .max_tokens_per_input(8)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    assert!(strict.tokenize(" token.".to_string()).is_ok());
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        strict.tokenize(long.clone()).err().unwrap().kind()
+    );
+
+    let truncating = BytePairEncoding::builder()
+        .max_tokens_per_input(8)
+        .on_token_overflow(TokenOverflow::Truncate)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    let tokens = truncating.tokenize(long.clone()).unwrap();
+    assert_eq!(8, tokens.len());
+    assert_eq!(
+        truncating.tokenize_truncated(long.clone(), 8).unwrap().0,
+        tokens
+    );
+
+    let sentinels_only = vec!["<|startoftext|>", "<|endoftext|>"];
+    assert_eq!(
+        sentinels_only,
+        truncating.tokenize_truncated(long.clone(), 2).unwrap().0
+    );
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        truncating
+            .tokenize_truncated(long.clone(), 0)
+            .err()
+            .unwrap()
+            .kind()
+    );
+
+    let minimal = BytePairEncoding::builder()
+        .max_tokens_per_input(2)
+        .on_token_overflow(TokenOverflow::Truncate)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    assert_eq!(sentinels_only, minimal.tokenize(long).unwrap());
+
+    let error = BytePairEncoding::builder()
+        .max_tokens_per_input(1)
+        .on_token_overflow(TokenOverflow::Truncate)
+        .train(TEXT.to_string(), 22)
+        .err()
+        .unwrap();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+}
+
+#[test]
+fn bpe_check_against_reports_only_mismatches() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let tokens = |tokens: &[&str]| tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+    let matching = tokenizer.tokenize(" token".to_string()).unwrap();
+    let cases = vec![
+        (" token".to_string(), matching),
+        (
+            "This".to_string(),
+            tokens(&["<|startoftext|>", "This", "<|endoftext|>"]),
+        ),
+    ];
+
+    assert_eq!(
+        vec![Mismatch {
+            input: "This".to_string(),
+            expected: tokens(&["<|startoftext|>", "This", "<|endoftext|>"]),
+            actual: Some(tokens(&[
+                "<|startoftext|>",
+                "T",
+                "h",
+                "is",
+                "<|endoftext|>"
+            ])),
+        }],
+        tokenizer.check_against(&cases)
+    );
+}
+
+#[test]
+fn bpe_from_documents_never_merges_across_documents() {
+    let documents = ["ab", "cd", "ab", "cd"].map(String::from).to_vec();
+    let spans_boundary = |tokenizer: &BytePairEncoding| {
+        tokenizer.merges.iter().any(|(left, right)| {
+            let merged = format!("{left}{right}");
+            merged.contains("bc") || merged.contains("da")
+        })
+    };
+
+    let concatenated = BytePairEncoding::from_chunks(documents.clone().into_iter(), 20).unwrap();
+    assert!(spans_boundary(&concatenated));
+
+    let tokenizer = BytePairEncoding::from_documents(documents, 20).unwrap();
+    assert!(!tokenizer.merges.is_empty());
+    assert!(!spans_boundary(&tokenizer));
+    assert_eq!(vec!["ab".to_string(), "cd".to_string()], {
+        let mut words = tokenizer
+            .tokenizer
+            .keys()
+            .filter(|word| word.len() > 1)
+            .cloned()
+            .collect::<Vec<String>>();
+        words.sort();
+        words
+    });
+}
+
+#[test]
+fn bpe_is_special_covers_sentinels_and_added_specials() {
+    let tokenizer = BytePairEncoding::builder()
+        .add_special_token("<|pad|>")
+        .add_special_token("<|pad|>")
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    assert!(tokenizer.is_special("<|endoftext|>"));
+    assert!(tokenizer.is_special("<|startoftext|>"));
+    assert!(tokenizer.is_special("<|pad|>"));
+    assert!(!tokenizer.is_special("token"));
+    assert_eq!(
+        vec!["<|startoftext|>", "<|endoftext|>", "<|pad|>"],
+        tokenizer.special_tokens()
+    );
+    assert_eq!(Some(2), tokenizer.token_to_id("<|pad|>"));
+    assert_eq!(tokenizer.special_tokens(), tokenizer.info().special_tokens);
+}
+
+#[test]
+fn bpe_render_leading_space_round_trips() {
+    let tokenizer = BytePairEncoding::builder()
+        .render_leading_space(true)
+        .train(TEXT.to_string(), 30)
+        .unwrap();
+
+    let tokens = tokenizer.tokenize(TEXT.to_string()).unwrap();
+    assert!(tokens.iter().all(|token| !token.starts_with(' ')));
+    assert!(tokens.iter().any(|token| token.starts_with('\u{2581}')));
+    assert_eq!(TEXT, tokenizer.detokenize(&tokens).unwrap());
+
+    let borrowed = tokenizer.tokenize_borrowed(TEXT.to_string()).unwrap();
+    assert_eq!(tokens, borrowed);
+    assert!(tokenizer.encode(TEXT.to_string()).is_ok());
+
+    let plain = BytePairEncoding::from(TEXT.to_string(), 30);
+    assert_eq!(
+        plain.tokenize(TEXT.to_string()).unwrap(),
+        tokens
+            .iter()
+            .map(|token| token.replace('\u{2581}', " "))
+            .collect::<Vec<String>>()
+    );
+}
+
+#[test]
+fn bpe_mark_continuations_separates_word_initial_and_interior_pieces() {
+    let corpus = "es es yes bes tes".to_string();
+    let tokenizer = BytePairEncoding::builder()
+        .mark_continuations(true)
+        .train(corpus.clone(), 15)
+        .unwrap();
+
+    assert_eq!(
+        vec!["<|startoftext|>", " ", "es", "<|endoftext|>"],
+        tokenizer.tokenize(" es".to_string()).unwrap()
+    );
+    assert_eq!(
+        vec!["<|startoftext|>", " ", "y", "##es", "<|endoftext|>"],
+        tokenizer.tokenize(" yes".to_string()).unwrap()
+    );
+    assert_ne!(tokenizer.token_to_id("es"), tokenizer.token_to_id("##es"));
+
+    let tokens = tokenizer.tokenize(corpus.clone()).unwrap();
+    assert_eq!(corpus, tokenizer.detokenize(&tokens).unwrap());
+    assert!(tokenizer.validate().is_ok());
+
+    let plain = BytePairEncoding::builder().train(corpus, 15).unwrap();
+    assert!(plain.token_to_id("##es").is_none());
+}
+
+#[test]
+fn bpe_max_alphabet_size_drops_the_rarest_characters() {
+    let corpus = "aaa bbb aab 字".to_string();
+    let tokenizer = BytePairEncoding::builder()
+        .max_alphabet_size(8)
+        .unknown_character(UnknownCharacter::Unk)
+        .unknown_word(UnknownWord::Replay)
+        .train(corpus.clone(), 10)
+        .unwrap();
+
+    assert_eq!(8, tokenizer.alphabet().len());
+    assert!(tokenizer.validate().is_ok());
+    assert!(tokenizer.alphabet().contains(&"a".to_string()));
+    assert!(tokenizer.alphabet().contains(&"b".to_string()));
+    assert!(!tokenizer.alphabet().contains(&"字".to_string()));
+    assert!(tokenizer
+        .tokenize(" 字".to_string())
+        .unwrap()
+        .contains(&"<|unk|>".to_string()));
+
+    let uncapped = BytePairEncoding::builder().train(corpus, 11).unwrap();
+    assert!(uncapped.alphabet().contains(&"字".to_string()));
+}
+
+#[test]
+fn bpe_max_alphabet_size_keeps_words_with_rare_characters() {
+    let corpus = "ab字ab ab字ab zz".to_string();
+
+    let unk = BytePairEncoding::builder()
+        .max_alphabet_size(8)
+        .unknown_character(UnknownCharacter::Unk)
+        .train(corpus.clone(), 10)
+        .unwrap();
+    assert!(!unk.alphabet().contains(&"字".to_string()));
+    assert_eq!(
+        Some(&vec![
+            " ab".to_string(),
+            "<|unk|>".to_string(),
+            "ab".to_string()
+        ]),
+        unk.tokenizer.get(" ab<|unk|>ab")
+    );
+    assert!(unk.validate().is_ok());
+
+    let bytes = BytePairEncoding::builder()
+        .max_alphabet_size(264)
+        .unknown_character(UnknownCharacter::ByteFallback)
+        .train(corpus, 300)
+        .unwrap();
+    assert!(!bytes.alphabet().contains(&"字".to_string()));
+    assert!(bytes
+        .merges
+        .contains(&("ab".to_string(), "<0xE5>".to_string())));
+    assert!(bytes.validate().is_ok());
+}
+
+#[test]
+fn bpe_inference_model_tokenizes_like_the_full_tokenizer() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let model = tokenizer.to_inference_model();
+
+    assert_eq!(
+        tokenizer.tokenize(TEXT.to_string()).unwrap(),
+        model.tokenize(TEXT.to_string()).unwrap()
+    );
+    assert_eq!(
+        tokenizer.encode(" token".to_string()).unwrap(),
+        model.encode(" token".to_string()).unwrap()
+    );
+
+    let json = model.to_json().unwrap();
+    assert!(json.len() < tokenizer.to_json().unwrap().len());
+    let loaded = json.parse::<InferenceModel>().unwrap();
+    let tokens = loaded.tokenize(TEXT.to_string()).unwrap();
+    assert_eq!(TEXT, loaded.detokenize(&tokens).unwrap());
+}
+
+#[test]
+fn bpe_trailing_punctuation_is_split_from_a_known_word() {
+    let tokenizer = BytePairEncoding::from("token token .".to_string(), 14);
+    assert!(!tokenizer.tokenizer.contains_key("token."));
+
+    let expected = [
+        vec!["<|startoftext|>".to_string()],
+        tokenizer.tokenizer["token"].clone(),
+        vec![".".to_string(), "<|endoftext|>".to_string()],
+    ]
+    .concat();
+    assert_eq!(expected, tokenizer.tokenize("token.".to_string()).unwrap());
+    assert_eq!(
+        "token.",
+        tokenizer
+            .detokenize(&tokenizer.tokenize("token.".to_string()).unwrap())
+            .unwrap()
+    );
+}
+
+#[test]
+fn bpe_tokenize_map_applies_the_function_to_every_token() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let tokens = tokenizer.tokenize(TEXT.to_string()).unwrap();
+
+    assert_eq!(
+        tokens,
+        tokenizer
+            .tokenize_map(TEXT.to_string(), |token| token)
+            .unwrap()
+    );
+    assert_eq!(
+        tokens
+            .iter()
+            .map(|token| token.to_uppercase())
+            .collect::<Vec<String>>(),
+        tokenizer
+            .tokenize_map(TEXT.to_string(), |token| token.to_uppercase())
+            .unwrap()
+    );
+}
+
+#[test]
+fn bpe_case_insensitive_specials_recognize_the_canonical_token() {
+    let text = "This<|EndOfText|> token".to_string();
+
+    let sensitive = BytePairEncoding::from(TEXT.to_string(), 22);
+    assert!(sensitive.tokenize(text.clone()).is_err());
+
+    let insensitive = BytePairEncoding::builder()
+        .case_insensitive_specials(true)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    let tokens = insensitive.tokenize(text).unwrap();
+    assert_eq!(
+        vec![
+            "<|startoftext|>",
+            "T",
+            "h",
+            "is",
+            "<|endoftext|>",
+            " ",
+            "token",
+            "<|endoftext|>"
+        ],
+        tokens
+    );
+}
+
+#[test]
+fn bpe_tokenize_matches_the_longest_overlapping_special() {
+    let tokenizer = BytePairEncoding::builder()
+        .add_special_token("<|s|>")
+        .add_special_token("<|s|>x")
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    assert_eq!(
+        vec!["<|startoftext|>", "<|s|>x", "<|s|>", "<|endoftext|>"],
+        tokenizer.tokenize("<|s|>x<|s|>".to_string()).unwrap()
+    );
+}
+
+#[test]
+fn bpe_estimate_training_cost_counts_the_requested_merges() {
+    let estimate = BytePairEncoding::estimate_training_cost(TEXT, 22);
+    let corpus = TrainedCorpus::new(TEXT.to_string());
+
+    assert_eq!(22 - (corpus.min_vocab_size() - 1), estimate.merges);
+    assert_eq!(
+        TrainingEstimate {
+            unique_words: 6,
+            alphabet_size: 16,
+            merges: 6,
+            complexity: 6 * 20,
+        },
+        estimate
+    );
+    assert_eq!(0, BytePairEncoding::estimate_training_cost(TEXT, 10).merges);
+}
+
+#[test]
+fn bpe_frozen_tokenizer_rejects_new_special_tokens() {
+    let mut tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    assert!(!tokenizer.is_frozen());
+    tokenizer.add_special_token("<|pad|>").unwrap();
+    assert!(tokenizer.is_special("<|pad|>"));
+
+    tokenizer.freeze();
+    assert!(tokenizer.is_frozen());
+    let vocab = tokenizer.vocab();
+
+    let error = tokenizer.add_special_token("<|sep|>").unwrap_err();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+    assert!(tokenizer.add_special_token("<|pad|>").is_ok());
+    assert_eq!(vocab, tokenizer.vocab());
+
+    let loaded = tokenizer
+        .to_json()
+        .unwrap()
+        .parse::<BytePairEncoding>()
+        .unwrap();
+    assert!(loaded.is_frozen());
+}
+
+#[test]
+fn bpe_split_on_unicode_whitespace_breaks_words_at_non_breaking_spaces() {
+    let corpus = "a\u{a0}token a\u{a0}token".to_string();
+
+    let split = BytePairEncoding::builder()
+        .split_on_unicode_whitespace(true)
+        .train(corpus.clone(), 14)
+        .unwrap();
+    assert!(split.tokenizer.contains_key("\u{a0}token"));
+    assert!(!split.tokenizer.contains_key("a\u{a0}token"));
+    assert!(split.pre_tokenizer().unicode_whitespace);
+
+    let joined = BytePairEncoding::builder().train(corpus, 14).unwrap();
+    assert!(joined.tokenizer.contains_key("a\u{a0}token"));
+}
+
+#[test]
+fn bpe_encode_with_tokens_pairs_each_token_with_its_id() {
+    let tokenizer = BytePairEncoding::builder()
+        .render_leading_space(true)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let (tokens, ids) = tokenizer.encode_with_tokens(TEXT.to_string()).unwrap();
+
+    assert_eq!(tokenizer.tokenize(TEXT.to_string()).unwrap(), tokens);
+    assert_eq!(tokenizer.encode(TEXT.to_string()).unwrap(), ids);
+    for (token, id) in tokens.iter().zip(ids.iter()) {
+        assert_eq!(
+            token.replace('\u{2581}', " "),
+            tokenizer.id_to_token(*id).unwrap()
+        );
+    }
+}
+
+#[test]
+fn bpe_group_punctuation_keeps_an_ellipsis_together() {
+    let corpus = "wait... ok...".to_string();
+
+    let grouped = BytePairEncoding::builder()
+        .group_punctuation(true)
+        .train(corpus.clone(), 14)
+        .unwrap();
+    assert_eq!(
+        vec!["<|startoftext|>", "...", "<|endoftext|>"],
+        grouped.tokenize("...".to_string()).unwrap()
+    );
+
+    let ungrouped = BytePairEncoding::builder().train(corpus, 14).unwrap();
+    assert_eq!(
+        vec!["<|startoftext|>", ".", ".", ".", "<|endoftext|>"],
+        ungrouped.tokenize("...".to_string()).unwrap()
+    );
+}
+
+#[test]
+fn bpe_required_words_tokenize_to_a_single_token() {
+    let tokenizer = BytePairEncoding::builder()
+        .required_words(vec!["C++".to_string()])
+        .unknown_character(UnknownCharacter::Unk)
+        .train("I write C++ code".to_string(), 18)
+        .unwrap();
+
+    assert_eq!(
+        vec!["<|startoftext|>", " ", "C++", "<|endoftext|>"],
+        tokenizer.tokenize(" C++".to_string()).unwrap()
+    );
+    assert_eq!(19, tokenizer.vocab_size);
+    assert_eq!(
+        Some(tokenizer.vocab().len() - 1),
+        tokenizer.token_to_id("C++")
+    );
+    assert!(!tokenizer.is_special("C++"));
+
+    let union = tokenizer.union(&BytePairEncoding::from(TEXT.to_string(), 22));
+    assert_eq!(
+        vec!["<|startoftext|>", "C++", "<|endoftext|>"],
+        union.tokenize("C++".to_string()).unwrap()
+    );
+}
+
+#[test]
+fn bpe_required_words_do_not_split_longer_words() {
+    let corpus = "the cat concatenate the cat".to_string();
+
+    let expected = BytePairEncoding::from(corpus.clone(), 30);
+    let tokenizer = BytePairEncoding::builder()
+        .required_words(vec!["cat".to_string()])
+        .train(corpus, 30)
+        .unwrap();
+
+    let actual = tokenizer.tokenize(" concatenate".to_string()).unwrap();
+    assert_eq!(
+        expected.tokenize(" concatenate".to_string()).unwrap(),
+        actual
+    );
+    assert_eq!(
+        actual.len(),
+        tokenizer.count_tokens(" concatenate".to_string()).unwrap()
+    );
+    assert_eq!(
+        vec!["<|startoftext|>", " ", "cat", "<|endoftext|>"],
+        tokenizer.tokenize(" cat".to_string()).unwrap()
+    );
+}
+
+#[test]
+fn bpe_nearest_known_word_recovers_a_misspelling() {
+    let corpus = "token is a token".to_string();
+
+    let tokenizer = BytePairEncoding::from(corpus.clone(), 18);
+    assert_eq!(Some("token"), tokenizer.nearest_known_word("tokn"));
+    assert_eq!(Some(" is"), tokenizer.nearest_known_word(" is"));
+
+    let strict = BytePairEncoding::builder()
+        .max_edit_distance(1)
+        .train(corpus, 18)
+        .unwrap();
+    assert_eq!(Some("token"), strict.nearest_known_word("tokn"));
+    assert_eq!(None, strict.nearest_known_word("completely"));
+}
+
+#[test]
+fn bpe_post_merge_rule_rejoins_a_number_and_its_unit() {
+    let corpus = "10kg 10 kg 10 kg".to_string();
+    let plain = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .train(corpus.clone(), 12)
+        .unwrap();
+    assert_eq!(
+        vec!["<|startoftext|>", "10", "kg", "<|endoftext|>"],
+        plain.tokenize("10kg".to_string()).unwrap()
+    );
+
+    let joined = BytePairEncoding::builder()
+        .unknown_word(UnknownWord::Replay)
+        .post_merge_rule(r"\d+", "kg|g")
+        .train(corpus.clone(), 12)
+        .unwrap();
+    assert_eq!(plain.vocab_size + 1, joined.vocab_size);
+    assert!(joined.validate().is_ok());
+    assert_eq!(
+        vec!["<|startoftext|>", "10kg", "<|endoftext|>"],
+        joined.tokenize("10kg".to_string()).unwrap()
+    );
+    assert_eq!(
+        vec!["<|startoftext|>", "10", " ", "kg", "<|endoftext|>"],
+        joined.tokenize("10 kg".to_string()).unwrap()
+    );
+    assert_eq!(
+        vec!["<|startoftext|>", "10kg", "kg", "<|endoftext|>"],
+        joined.tokenize("10kgkg".to_string()).unwrap()
+    );
+
+    let text = "10kg 10kgkg".to_string();
+    let tokens = joined.tokenize(text.clone()).unwrap();
+    let (encoded, ids) = joined.encode_with_tokens(text.clone()).unwrap();
+    assert_eq!(tokens, encoded);
+    assert_eq!(tokens.len(), ids.len());
+    assert_eq!(tokens.len(), joined.count_tokens(text.clone()).unwrap());
+    assert_eq!(
+        tokens,
+        joined
+            .tokenize_borrowed(text.clone())
+            .unwrap()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<String>>()
+    );
+    assert_eq!(
+        tokens,
+        joined
+            .tokenize_grouped(text)
+            .unwrap()
+            .into_iter()
+            .flat_map(|(_, tokens)| tokens)
+            .collect::<Vec<String>>()
+    );
+
+    let loaded = joined
+        .to_json()
+        .unwrap()
+        .parse::<BytePairEncoding>()
+        .unwrap();
+    assert_eq!(joined.vocab(), loaded.vocab());
+    assert_eq!(
+        vec!["<|startoftext|>", "10kg", "<|endoftext|>"],
+        loaded.tokenize("10kg".to_string()).unwrap()
+    );
+
+    let error = BytePairEncoding::builder()
+        .post_merge_rule("(", "kg")
+        .train(corpus, 12)
+        .err()
+        .unwrap();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+}
+
+#[test]
+fn bpe_builder_clone_is_independent_of_the_original() {
+    let original = BytePairEncoding::builder().lowercase_ascii(true);
+    let mutated = original.clone().unknown_word(UnknownWord::Replay);
+
+    let tokenizer = original.train(TEXT.to_string(), 22).unwrap();
+    assert!(tokenizer.normalizer().lowercase_ascii);
+    assert!(tokenizer.tokenize("tokens".to_string()).is_err());
+
+    let tokenizer = mutated.train(TEXT.to_string(), 22).unwrap();
+    assert!(tokenizer.tokenize("tokens".to_string()).is_ok());
+
+    let reset = BytePairEncoding::builder()
+        .lowercase_ascii(true)
+        .reset()
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    assert_eq!(Normalizer::default(), *reset.normalizer());
+}
+
+#[test]
+fn bpe_tokenize_with_word_starts_marks_the_first_token_of_each_word() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let (tokens, starts) = tokenizer
+        .tokenize_with_word_starts(TEXT.to_string())
+        .unwrap();
+
+    assert_eq!(tokenizer.tokenize(TEXT.to_string()).unwrap(), tokens);
+    assert_eq!(vec![1, 4, 6, 9, 11, 13], starts);
+    assert_eq!(
+        vec!["T", " ", " ", " ", " ", "."],
+        starts
+            .iter()
+            .map(|start| tokens[*start].as_str())
+            .collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn bpe_to_rust_source_embeds_the_vocab_and_merges() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let source = tokenizer.to_rust_source("embedded").unwrap();
+
+    assert!(source.starts_with("pub mod embedded {\n"));
+    assert!(source.contains("pub const MERGES: &[(&str, &str)] = &[\n"));
+    for (left, right) in tokenizer.merges.iter() {
+        assert!(source.contains(&format!("    ({left:?}, {right:?}),\n")));
+    }
+    assert!(source.contains("    \"\\n\",\n"));
+    assert!(source.contains("    \"<|startoftext|>\",\n"));
+    assert_eq!(include_str!("fixtures/embedded.rs"), source);
+}
+
+#[test]
+fn bpe_from_rust_consts_rebuilds_the_embedded_tokenizer() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let embedded = BytePairEncoding::from_rust_consts(
+        embedded::CONFIG,
+        embedded::VOCAB,
+        embedded::ALPHABET,
+        embedded::MERGES,
+    )
+    .unwrap();
+    assert_eq!(tokenizer.vocab(), embedded.vocab());
+    assert_eq!(tokenizer.special_tokens(), embedded::SPECIAL_TOKENS);
+    assert_eq!(tokenizer.reserved_ids().end, embedded::RESERVED_IDS);
+    assert_eq!(
+        tokenizer.tokenize(TEXT.to_string()).unwrap(),
+        embedded.tokenize(TEXT.to_string()).unwrap()
+    );
+
+    let error = BytePairEncoding::from_rust_consts(
+        embedded::CONFIG,
+        &embedded::VOCAB[1..],
+        embedded::ALPHABET,
+        embedded::MERGES,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(ErrorKind::InvalidData, error.kind());
+}
+
+#[test]
+fn bpe_split_leading_boundary_controls_a_leading_space() {
+    let corpus = " test a test".to_string();
+
+    let attached = BytePairEncoding::from(corpus.clone(), 15);
+    assert_eq!(
+        vec!["<|startoftext|>", " test", "<|endoftext|>"],
+        attached.tokenize(" test".to_string()).unwrap()
+    );
+
+    let split = BytePairEncoding::builder()
+        .split_leading_boundary(true)
+        .unknown_word(UnknownWord::Replay)
+        .train(corpus.clone(), 15)
+        .unwrap();
+    assert!(split.tokenizer.contains_key("test"));
+    assert_eq!(
+        vec!["<|startoftext|>", " ", "test", "<|endoftext|>"],
+        split.tokenize(" test".to_string()).unwrap()
+    );
+
+    let chunked = BytePairEncoding::builder()
+        .split_leading_boundary(true)
+        .unknown_word(UnknownWord::Replay)
+        .prepare_chunks(vec![" test a te".to_string(), "st".to_string()].into_iter())
+        .retrain_with_vocab_size(15)
+        .unwrap();
+    assert_eq!(split.merges, chunked.merges);
+    assert_eq!(split.tokenizer, chunked.tokenizer);
+    assert_eq!(
+        split.tokenize(corpus.clone()).unwrap(),
+        chunked.tokenize(corpus).unwrap()
+    );
+}
+
+#[test]
+fn bpe_oov_rate_over_files_aggregates_words_across_files() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let dir = env::temp_dir();
+    let paths = vec![
+        dir.join("tokenizers_rs_oov_known.txt"),
+        dir.join("tokenizers_rs_oov_mixed.txt"),
+        dir.join("tokenizers_rs_oov_empty.txt"),
+    ];
+    fs::write(&paths[0], "This is a token.").unwrap();
+    fs::write(&paths[1], "This was not.").unwrap();
+    fs::write(&paths[2], "").unwrap();
+
+    let rate = tokenizer.oov_rate_over_files(&paths).unwrap();
+    for path in paths {
+        fs::remove_file(path).unwrap();
+    }
+
+    assert!((rate - 1.0 / 9.0).abs() < 1e-9);
+}
+
+#[test]
+fn bpe_without_special_tokens_has_no_sentinels() {
+    let tokenizer = BytePairEncoding::builder()
+        .with_special_tokens(false)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    let with_specials = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let vocab = tokenizer.vocab();
+    assert!(vocab.iter().all(|token| !token.starts_with("<|")));
+    assert_eq!(with_specials.vocab().len() - 2, vocab.len());
+    assert_eq!(with_specials.vocab_size, tokenizer.vocab_size);
+    assert!(tokenizer.special_tokens().is_empty());
+
+    let tokenized = tokenizer.tokenize(TEXT.to_string()).unwrap();
+    assert_eq!(
+        vec!["T", "h", "is", " ", "is", " ", "n", "ot", " ", "a", " ", "token", "."],
+        tokenized
+    );
+    assert_eq!(13, tokenizer.count_tokens(TEXT.to_string()).unwrap());
+    assert_eq!(TEXT, tokenizer.detokenize(&tokenized).unwrap());
+}
+
+#[test]
+fn bpe_char_coverage_counts_characters_in_the_alphabet() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    assert_eq!(1.0, tokenizer.char_coverage("not a token"));
+    assert_eq!(0.5, tokenizer.char_coverage("toÿé"));
+    assert_eq!(1.0, tokenizer.char_coverage(""));
+}
+
+#[test]
+fn bpe_save_produces_identical_files_for_the_same_tokenizer() {
+    let corpus = "the cat sat on the mat with the hat".to_string();
+    let tokenizer = BytePairEncoding::from(corpus.clone(), 24);
+    let retrained = BytePairEncoding::from(corpus, 24);
+
+    let dir = env::temp_dir();
+    let first = dir.join("tokenizers_rs_stable_first.json");
+    let second = dir.join("tokenizers_rs_stable_second.json");
+    let third = dir.join("tokenizers_rs_stable_third.json");
+    tokenizer.save(&first).unwrap();
+    tokenizer.save(&second).unwrap();
+    retrained.save(&third).unwrap();
+
+    let saved = [&first, &second, &third].map(|path| fs::read(path).unwrap());
+    for path in [first, second, third] {
+        fs::remove_file(path).unwrap();
+    }
+
+    assert_eq!(saved[0], saved[1]);
+    assert_eq!(saved[0], saved[2]);
+}
+
+#[test]
+fn bpe_merge_strategies_diverge_on_overlapping_merges() {
+    let train = |strategy| {
+        BytePairEncoding::builder()
+            .merge_strategy(strategy)
+            .unknown_word(UnknownWord::Replay)
+            .train("bc bc bc abab".to_string(), 12)
+            .unwrap()
+    };
+    let priority = train(MergeStrategy::Priority);
+    let greedy = train(MergeStrategy::GreedyLeftToRight);
+    assert_eq!(
+        vec![
+            ("b".to_string(), "c".to_string()),
+            ("a".to_string(), "b".to_string()),
+            (" ".to_string(), "bc".to_string()),
+        ],
+        priority.merges
+    );
+
+    assert_eq!(
+        vec!["<|startoftext|>", "a", "bc", "<|endoftext|>"],
+        priority.tokenize("abc".to_string()).unwrap()
+    );
+    assert_eq!(
+        vec!["<|startoftext|>", "ab", "c", "<|endoftext|>"],
+        greedy.tokenize("abc".to_string()).unwrap()
+    );
+}
+
+#[test]
+fn bpe_greedy_merge_strategy_applies_to_training_words() {
+    let greedy = BytePairEncoding::builder()
+        .merge_strategy(MergeStrategy::GreedyLeftToRight)
+        .unknown_word(UnknownWord::Replay)
+        .train("bc bc bc abab abc".to_string(), 12)
+        .unwrap();
+
+    let actual = greedy.tokenize(" abc".to_string()).unwrap();
+    assert_eq!(
+        vec!["<|startoftext|>", " ", "ab", "c", "<|endoftext|>"],
+        actual
+    );
+    assert_eq!(
+        vec![
+            "<|startoftext|>",
+            " ",
+            "ab",
+            "c",
+            "ab",
+            "c",
+            "<|endoftext|>"
+        ],
+        greedy.tokenize(" abcabc".to_string()).unwrap()
+    );
+    assert_eq!(
+        actual,
+        greedy
+            .to_inference_model()
+            .tokenize(" abc".to_string())
+            .unwrap()
+    );
+    assert_eq!(
+        actual,
+        greedy
+            .to_json()
+            .unwrap()
+            .parse::<BytePairEncoding>()
+            .unwrap()
+            .tokenize(" abc".to_string())
+            .unwrap()
+    );
+}
+
+#[test]
+fn bpe_self_check_specials_detects_specials_that_do_not_survive() {
+    let mut tokenizer = BytePairEncoding::builder()
+        .case_insensitive_specials(true)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    tokenizer.add_special_token("<|sep|>").unwrap();
+    assert!(tokenizer.self_check_specials().is_ok());
+
+    tokenizer.add_special_token("<|SEP|>").unwrap();
+    let error = tokenizer.self_check_specials().unwrap_err();
+    assert_eq!(ErrorKind::InvalidData, error.kind());
+}
+
+#[test]
+fn bpe_encode_padded_places_padding_on_the_requested_side() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+    let encoded = tokenizer.encode("This".to_string()).unwrap();
+    assert_eq!(5, encoded.len());
+
+    let (left, left_mask) = tokenizer
+        .encode_padded("This".to_string(), 8, 0, PaddingSide::Left)
+        .unwrap();
+    assert_eq!(vec![0, 0, 0, 1, 1, 1, 1, 1], left_mask);
+    assert_eq!([0, 0, 0], left[..3]);
+    assert_eq!(encoded, left[3..]);
+
+    let (right, right_mask) = tokenizer
+        .encode_padded("This".to_string(), 8, 0, PaddingSide::Right)
+        .unwrap();
+    assert_eq!(vec![1, 1, 1, 1, 1, 0, 0, 0], right_mask);
+    assert_eq!(encoded, right[..5]);
+    assert_eq!([0, 0, 0], right[5..]);
+
+    let error = tokenizer
+        .encode_padded("This".to_string(), 4, 0, PaddingSide::Left)
+        .unwrap_err();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+}
+
+#[test]
+fn bpe_token_histogram_counts_subwords_without_sentinels() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let histogram = tokenizer.token_histogram(TEXT.to_string()).unwrap();
+    let tokenized = tokenizer.tokenize(TEXT.to_string()).unwrap();
+
+    assert_eq!(tokenized.len() - 2, histogram.values().sum::<usize>());
+    assert_eq!(Some(&2), histogram.get("is"));
+    assert_eq!(Some(&4), histogram.get(" "));
+    assert_eq!(None, histogram.get("<|startoftext|>"));
+}
+
+#[test]
+fn bpe_special_ids_after_vocab_gives_sentinels_the_highest_ids() {
+    let mut tokenizer = BytePairEncoding::builder()
+        .special_ids_after_vocab(true)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+    let interleaved = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    let vocab = tokenizer.vocab();
+    assert_eq!(interleaved.vocab().len(), vocab.len());
+    assert_eq!(
+        Some(vocab.len() - 2),
+        tokenizer.token_to_id("<|startoftext|>")
+    );
+    assert_eq!(
+        Some(vocab.len() - 1),
+        tokenizer.token_to_id("<|endoftext|>")
+    );
+    assert_eq!(Some(0), tokenizer.token_to_id(&interleaved.alphabet()[0]));
+
+    let encoded = tokenizer.encode(TEXT.to_string()).unwrap();
+    assert_eq!(vocab.len() - 2, encoded[0]);
+    assert_eq!(vocab.len() - 1, encoded[encoded.len() - 1]);
+
+    tokenizer.add_special_token("<|pad|>").unwrap();
+    assert_eq!(
+        Some(vocab.len() - 1),
+        tokenizer.token_to_id("<|endoftext|>")
+    );
+    assert_eq!(Some(vocab.len()), tokenizer.token_to_id("<|pad|>"));
+    assert_eq!(encoded, tokenizer.encode(TEXT.to_string()).unwrap());
+}
+
+#[test]
+fn bpe_tokenize_until_stops_at_the_stop_token() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    assert_eq!(
+        vec!["<|startoftext|>", "T", "h", "is", " ", "is"],
+        tokenizer.tokenize_until(TEXT.to_string(), "not").unwrap()
+    );
+    assert_eq!(
+        tokenizer.tokenize(TEXT.to_string()).unwrap(),
+        tokenizer.tokenize_until(TEXT.to_string(), "never").unwrap()
+    );
+}
+
+#[test]
+fn bpe_training_config_survives_save_and_load() {
+    let tokenizer = BytePairEncoding::builder()
+        .lowercase_ascii(true)
+        .isolate_punctuation(true)
+        .add_prefix_space(true)
+        .top_k_words(10)
+        .train(TEXT.to_string(), 22)
+        .unwrap();
+
+    let path = env::temp_dir().join("tokenizers_rs_training_config.json");
+    tokenizer.save(&path).unwrap();
+    let loaded = BytePairEncoding::load(&path).unwrap();
+    fs::remove_file(path).unwrap();
+
+    let expected = TrainingConfig {
+        max_vocab_size: 22,
+        normalizer: Normalizer {
+            lowercase_ascii: true,
+            ..Default::default()
+        },
+        pre_tokenizer: PreTokenizer {
+            isolate_punctuation: true,
+            ..Default::default()
+        },
+        add_prefix_space: true,
+        top_k_words: Some(10),
+        ..Default::default()
+    };
+    assert_eq!(&expected, tokenizer.training_config());
+    assert_eq!(&expected, loaded.training_config());
+}
+
+#[test]
+fn bpe_special_token_only_corpus_is_rejected() {
+    let error = BytePairEncoding::builder()
+        .train("<|endoftext|>".to_string(), 20)
+        .err()
+        .unwrap();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+
+    let error = BytePairEncoding::builder()
+        .add_special_token("<|sep|>")
+        .train("<|sep|><|startoftext|>\n<|sep|>".to_string(), 20)
+        .err()
+        .unwrap();
+    assert_eq!(ErrorKind::InvalidInput, error.kind());
+
+    let tokenizer = BytePairEncoding::builder()
+        .train(format!("<|startoftext|>{TEXT}<|endoftext|>"), 22)
+        .unwrap();
+    assert_eq!(
+        BytePairEncoding::from(TEXT.to_string(), 22).alphabet(),
+        tokenizer.alphabet()
+    );
+}
+
+#[test]
+fn bpe_special_tokens_in_the_corpus_split_words() {
+    let tokenizer = BytePairEncoding::from("ab<|endoftext|>cd".to_string(), 20);
+
+    assert!(tokenizer.tokenizer.contains_key("ab"));
+    assert!(tokenizer.tokenizer.contains_key("cd"));
+    assert!(!tokenizer.tokenizer.contains_key("abcd"));
+
+    let insensitive = BytePairEncoding::builder()
+        .case_insensitive_specials(true)
+        .train("ab <|EndOfText|> ab".to_string(), 12)
+        .unwrap();
+    for piece in ["<", "|", "E", "O", "T", "x"] {
+        assert!(!insensitive.alphabet().contains(&piece.to_string()));
+    }
+}
+
+#[test]
+fn bpe_trace_merges_lists_the_merges_applied_to_a_word() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 22);
+
+    assert_eq!(
+        vec![
+            ("t".to_string(), "o".to_string()),
+            ("to".to_string(), "k".to_string()),
+            ("tok".to_string(), "e".to_string()),
+            ("toke".to_string(), "n".to_string()),
+        ],
+        tokenizer.trace_merges(" token").unwrap()
+    );
+    assert!(tokenizer.trace_merges("a").unwrap().is_empty());
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        tokenizer.trace_merges("z").err().unwrap().kind()
+    );
+}
+
+#[test]
+fn bpe_character_set_guarantees_its_characters_in_the_alphabet() {
+    let tokenizer = BytePairEncoding::builder()
+        .include_character_set(CharacterSet::Digits)
+        .unknown_word(UnknownWord::Replay)
+        .train(TEXT.to_string(), 32)
+        .unwrap();
+
+    for digit in '0'..='9' {
+        assert!(tokenizer.alphabet().contains(&digit.to_string()));
+    }
+    assert_eq!(26, tokenizer.alphabet().len());
+    assert!(tokenizer.tokenize(" 2024".to_string()).is_ok());
+}
+
+#[test]
+fn bpe_deduplicate_documents_counts_each_document_once() {
+    let unique = "the cat sat\nthe dog ran\n".to_string();
+    let duplicated = format!("{}the dog ran\n", "the cat sat\n".repeat(5));
+    let train = |corpus: String, enabled| {
+        BytePairEncoding::builder()
+            .deduplicate_documents(enabled)
+            .train(corpus, 24)
+            .unwrap()
+    };
+
+    let expected = train(unique, false).merges;
+    assert_eq!(expected, train(duplicated.clone(), true).merges);
+    assert_ne!(expected, train(duplicated.clone(), false).merges);
+
+    let chunks = duplicated
+        .as_bytes()
+        .chunks(5)
+        .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+        .collect::<Vec<String>>();
+    assert_eq!(
+        expected,
+        BytePairEncoding::builder()
+            .deduplicate_documents(true)
+            .prepare_chunks(chunks.into_iter())
+            .retrain_with_vocab_size(24)
+            .unwrap()
+            .merges
+    );
+
+    let documents = vec!["the cat sat".to_string(), "the dog ran".to_string()];
+    let mut repeated = vec![documents[0].clone(); 5];
+    repeated.push(documents[1].clone());
+    let builder = BytePairEncoding::builder().deduplicate_documents(true);
+    assert_eq!(
+        builder
+            .prepare_documents(documents)
+            .retrain_with_vocab_size(24)
+            .unwrap()
+            .merges,
+        builder
+            .prepare_documents(repeated)
+            .retrain_with_vocab_size(24)
+            .unwrap()
+            .merges
+    );
+}
+
+#[test]
+fn bpe_longest_token_returns_the_longest_learned_subword() {
+    let tokenizer = BytePairEncoding::from(TEXT.to_string(), 30);
+
+    assert_eq!(Some((" token".to_string(), 6)), tokenizer.longest_token());
+}
+
+#[test]
+fn bpe_strip_invisible_marks_keeps_zero_width_joiners_out_of_the_alphabet() {
+    let corpus = "This is\u{200D} not a token.".to_string();
+
+    let preserved = BytePairEncoding::from(corpus.clone(), 22);
+    assert!(preserved.alphabet().contains(&"\u{200D}".to_string()));
+
+    let stripped = BytePairEncoding::builder()
+        .strip_invisible_marks(true)
+        .train(corpus, 22)
+        .unwrap();
+    assert!(!stripped.alphabet().contains(&"\u{200D}".to_string()));
+    assert_eq!(
+        BytePairEncoding::from(TEXT.to_string(), 22).alphabet(),
+        stripped.alphabet()
+    );
+}
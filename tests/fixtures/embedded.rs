@@ -0,0 +1,60 @@
+pub mod embedded {
+pub const CONFIG: &str = "{\"max_vocab_size\":22,\"normalizer\":{\"lowercase_ascii\":false,\"strip_accents\":false,\"strip_control_characters\":false,\"strip_invisible_marks\":false},\"pre_tokenizer\":{\"isolate_punctuation\":false,\"unicode_whitespace\":false,\"group_punctuation\":false,\"split_leading_boundary\":false},\"initial_alphabet\":[],\"character_sets\":[],\"estimated_unique_words\":0,\"isolate_scripts\":false,\"add_prefix_space\":false,\"trim_corpus\":false,\"deduplicate_documents\":false,\"top_k_words\":null,\"max_alphabet_size\":null,\"mark_continuations\":false,\"required_words\":[],\"unknown_word\":\"Error\",\"unknown_character\":\"Error\",\"merge_strategy\":\"Priority\",\"reserved_ids\":0,\"merge_exhaustion\":\"StopSilently\",\"max_tokens_per_input\":null,\"token_overflow\":\"Error\",\"special_tokens\":[],\"render_leading_space\":false,\"case_insensitive_specials\":false,\"max_edit_distance\":null,\"cache_capacity\":0,\"post_merge_rules\":[],\"skip_special_tokens\":false,\"special_ids_after_vocab\":false}";
+pub const RESERVED_IDS: usize = 0;
+pub const VOCAB: &[&str] = &[
+    "<|startoftext|>",
+    "<|endoftext|>",
+    "\n",
+    " ",
+    "!",
+    ",",
+    ".",
+    "?",
+    "T",
+    "a",
+    "e",
+    "h",
+    "i",
+    "k",
+    "n",
+    "o",
+    "s",
+    "t",
+    "is",
+    "to",
+    "tok",
+    "toke",
+    "token",
+    "ot",
+];
+pub const SPECIAL_TOKENS: &[&str] = &[
+    "<|startoftext|>",
+    "<|endoftext|>",
+];
+pub const ALPHABET: &[&str] = &[
+    "\n",
+    " ",
+    "!",
+    ",",
+    ".",
+    "?",
+    "T",
+    "a",
+    "e",
+    "h",
+    "i",
+    "k",
+    "n",
+    "o",
+    "s",
+    "t",
+];
+pub const MERGES: &[(&str, &str)] = &[
+    ("i", "s"),
+    ("t", "o"),
+    ("to", "k"),
+    ("tok", "e"),
+    ("toke", "n"),
+    ("o", "t"),
+];
+}